@@ -0,0 +1,50 @@
+/* Translates the Intcode programs in data/ to Rust ahead of time; see
+ * src/transpile.rs.
+ */
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/analysis.rs"]
+mod analysis;
+#[allow(dead_code)]
+#[path = "src/transpile.rs"]
+mod transpile;
+
+type Patch = &'static [(usize, i64)];
+
+/* Each program, with any cells changed before it runs: day 2's noun and
+ * verb, and the quarters which let day 13's game be played.
+ */
+const PROGRAMS: &[(&str, &str, Patch)] = &[
+    ("input-day2", "Day2", &[(1, 12), (2, 2)]),
+    ("input-day5", "Day5", &[]),
+    ("input-day7", "Day7", &[]),
+    ("input-day9", "Day9", &[]),
+    ("day11-input", "Day11", &[]),
+    ("day13-input", "Day13", &[]),
+    ("day13-input", "Day13Play", &[(0, 2)]),
+    ("day15-input", "Day15", &[]),
+];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for (file, name, patch) in PROGRAMS {
+        let path = Path::new("data").join(file);
+        println!("cargo:rerun-if-changed={}", path.display());
+        let source = fs::read_to_string(&path).expect(file);
+        let mut program: Vec<i64> = source
+            .split(',')
+            .map(|s| s.trim().parse().expect(file))
+            .collect();
+        for &(address, value) in patch.iter() {
+            program[address] = value;
+        }
+        let code = transpile::transpile(&program, name, "adventofcode2019");
+        let dest = Path::new(&out_dir).join(format!("{}.rs", name.to_lowercase()));
+        fs::write(dest, code).unwrap();
+    }
+    println!("cargo:rerun-if-changed=src/analysis.rs");
+    println!("cargo:rerun-if-changed=src/transpile.rs");
+}
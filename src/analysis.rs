@@ -0,0 +1,638 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    Add,
+    Mul,
+    In,
+    Out,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+impl Op {
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            1 => Some(Op::Add),
+            2 => Some(Op::Mul),
+            3 => Some(Op::In),
+            4 => Some(Op::Out),
+            5 => Some(Op::JumpIfTrue),
+            6 => Some(Op::JumpIfFalse),
+            7 => Some(Op::LessThan),
+            8 => Some(Op::Equals),
+            9 => Some(Op::AdjustBase),
+            99 => Some(Op::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Op::Add => 1,
+            Op::Mul => 2,
+            Op::In => 3,
+            Op::Out => 4,
+            Op::JumpIfTrue => 5,
+            Op::JumpIfFalse => 6,
+            Op::LessThan => 7,
+            Op::Equals => 8,
+            Op::AdjustBase => 9,
+            Op::Halt => 99,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => 3,
+            Op::JumpIfTrue | Op::JumpIfFalse => 2,
+            Op::In | Op::Out | Op::AdjustBase => 1,
+            Op::Halt => 0,
+        }
+    }
+
+    /* Index of the parameter this instruction writes through, if any. */
+    pub fn output(self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => Some(2),
+            Op::In => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Mul => "mul",
+            Op::In => "in",
+            Op::Out => "out",
+            Op::JumpIfTrue => "jnz",
+            Op::JumpIfFalse => "jz",
+            Op::LessThan => "lt",
+            Op::Equals => "eq",
+            Op::AdjustBase => "arb",
+            Op::Halt => "halt",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Param {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+impl Param {
+    pub fn immediate(value: i64) -> Self {
+        Param {
+            mode: Mode::Immediate,
+            value,
+        }
+    }
+
+    pub fn position(address: usize) -> Self {
+        Param {
+            mode: Mode::Position,
+            value: address as i64,
+        }
+    }
+
+    pub fn constant(&self) -> Option<i64> {
+        match self.mode {
+            Mode::Immediate => Some(self.value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb - {}]", -self.value),
+            Mode::Relative => write!(f, "[rb + {}]", self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: usize,
+    pub op: Op,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    pub fn decode(memory: &[i64], address: usize) -> Option<Self> {
+        let instruction = *memory.get(address)?;
+        if instruction < 0 {
+            return None;
+        }
+        let op = Op::from_code(instruction % 100)?;
+        let mut modes = instruction / 100;
+        let mut params = Vec::with_capacity(op.arity());
+        for i in 0..op.arity() {
+            let mode = match modes % 10 {
+                0 => Mode::Position,
+                1 if op.output() != Some(i) => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => return None,
+            };
+            modes /= 10;
+            params.push(Param {
+                mode,
+                value: *memory.get(address + 1 + i)?,
+            });
+        }
+        if modes != 0 {
+            return None;
+        }
+        Some(Self {
+            address,
+            op,
+            params,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let mut modes = 0;
+        for param in self.params.iter().rev() {
+            modes = modes * 10
+                + match param.mode {
+                    Mode::Position => 0,
+                    Mode::Immediate => 1,
+                    Mode::Relative => 2,
+                };
+        }
+        let mut cells = vec![modes * 100 + self.op.code()];
+        cells.extend(self.params.iter().map(|p| p.value));
+        cells
+    }
+
+    pub fn len(&self) -> usize {
+        1 + self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn next(&self) -> usize {
+        self.address + self.len()
+    }
+
+    pub fn cells(&self) -> std::ops::Range<usize> {
+        self.address..self.next()
+    }
+
+    pub fn is_jump(&self) -> bool {
+        matches!(self.op, Op::JumpIfTrue | Op::JumpIfFalse)
+    }
+
+    /* The value an arithmetic or comparison instruction stores, if all its
+     * inputs are immediate.
+     */
+    pub fn constant_result(&self) -> Option<i64> {
+        let a = self.params.first()?.constant()?;
+        let b = self.params.get(1)?.constant()?;
+        match self.op {
+            Op::Add => Some(a + b),
+            Op::Mul => Some(a * b),
+            Op::LessThan => Some((a < b) as i64),
+            Op::Equals => Some((a == b) as i64),
+            _ => None,
+        }
+    }
+
+    /* Whether a jump is taken regardless of its condition, if that can be
+     * told from the instruction alone.
+     */
+    pub fn taken(&self) -> Option<bool> {
+        let condition = self.params[0].constant()?;
+        match self.op {
+            Op::JumpIfTrue => Some(condition != 0),
+            Op::JumpIfFalse => Some(condition == 0),
+            _ => None,
+        }
+    }
+
+    /* Addresses execution may continue at, where they are known statically. */
+    pub fn successors(&self) -> Vec<usize> {
+        match self.op {
+            Op::Halt => vec![],
+            Op::JumpIfTrue | Op::JumpIfFalse => {
+                let mut succs = Vec::new();
+                if self.taken() != Some(false) {
+                    if let Some(target) = self.params[1].constant() {
+                        if target >= 0 {
+                            succs.push(target as usize);
+                        }
+                    }
+                }
+                if self.taken() != Some(true) {
+                    succs.push(self.next());
+                }
+                succs
+            }
+            _ => vec![self.next()],
+        }
+    }
+
+    /* A jump whose target is computed at run time, such as a return. */
+    pub fn is_indirect(&self) -> bool {
+        self.is_jump() && self.taken() != Some(false) && self.params[1].constant().is_none()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic())?;
+        for (i, param) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+        Ok(())
+    }
+}
+
+/* What can be recovered about a program without running it.
+ *
+ * Code is found by following control flow from address 0.  Jumps through
+ * memory (typically returns, whose address was pushed with an immediate
+ * operand) can't be followed, so any immediate operand which is stored to
+ * memory and points at something that decodes is also treated as an entry
+ * point.
+ *
+ * Position-mode parameters name fixed addresses, so they tell us which cells
+ * may be read and written, unless the parameter itself may be overwritten.
+ * Relative-mode parameters do too if nothing moves the relative base.
+ * Otherwise only the lowest the base can be is tracked, so a relative-mode
+ * write could land anywhere above that: usually the stack, past the end of
+ * the code.  A write through an operand the program sets itself could land
+ * anywhere too, unless it looks like indexing a table, when it's taken to
+ * land past the table's start.
+ */
+pub struct Analysis {
+    pub program: Vec<i64>,
+    pub instructions: BTreeMap<usize, Instruction>,
+    pub entries: BTreeSet<usize>,
+    pub writes: BTreeSet<usize>,
+    pub reads: BTreeSet<usize>,
+    /* If some write goes through an operand which is itself written, or is
+     * relative to a base which moves, the lowest cell it could land in.
+     */
+    pub dynamic_writes: Option<usize>,
+    pub dynamic_reads: bool,
+    /* The relative base, if no instruction ever moves it. */
+    pub relative_base: Option<i64>,
+}
+
+impl Analysis {
+    pub fn new(program: &[i64]) -> Self {
+        Self::with_entries(program, &[])
+    }
+
+    /* As new(), but also exploring from the given addresses, for callers
+     * which know of code reached in ways the analysis can't see.
+     */
+    pub fn with_entries(program: &[i64], extra: &[usize]) -> Self {
+        let mut instructions = BTreeMap::new();
+        let mut entries: BTreeSet<usize> = extra.iter().cloned().collect();
+        entries.insert(0);
+        let mut pending: Vec<usize> = entries.iter().cloned().collect();
+
+        while !pending.is_empty() {
+            while let Some(address) = pending.pop() {
+                if instructions.contains_key(&address) {
+                    continue;
+                }
+                if let Some(instruction) = Instruction::decode(program, address) {
+                    pending.extend(instruction.successors());
+                    instructions.insert(address, instruction);
+                }
+            }
+            /* Look for code pointers among what we've found so far. */
+            let covered: BTreeSet<usize> = instructions.values().flat_map(|i| i.cells()).collect();
+            for instruction in instructions.values() {
+                if let Some(target) = instruction.constant_result() {
+                    if target > 0
+                        && !covered.contains(&(target as usize))
+                        && Instruction::decode(program, target as usize).is_some()
+                    {
+                        entries.insert(target as usize);
+                        pending.push(target as usize);
+                    }
+                }
+            }
+        }
+
+        let mut analysis = Self {
+            program: program.to_vec(),
+            instructions,
+            entries,
+            writes: BTreeSet::new(),
+            reads: BTreeSet::new(),
+            dynamic_writes: None,
+            dynamic_reads: false,
+            relative_base: None,
+        };
+        analysis.find_accesses();
+        analysis
+    }
+
+    /* The lowest the relative base can be at each instruction, following
+     * the code from the start with the base at 0.  Returns are jumps through
+     * memory, which can't be followed, so each code pointer is taken to be
+     * reached with the base it had when the pointer was stored.  That holds
+     * for calls as compiled code makes them, where each function puts the
+     * base back before it returns.  None where the base isn't bounded,
+     * because it's moved by something other than a constant or keeps
+     * falling round a loop.
+     */
+    fn base_floors(&self) -> BTreeMap<usize, Option<i64>> {
+        let mut floors: BTreeMap<usize, Option<i64>> = BTreeMap::new();
+        let mut lowered: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut pending = vec![(0, Some(0))];
+        while let Some((address, floor)) = pending.pop() {
+            let instruction = match self.instructions.get(&address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let floor = match (floors.get(&address), floor) {
+                (None, floor) => floor,
+                (Some(Some(old)), Some(new)) if new < *old => Some(new),
+                (Some(Some(_)), None) => None,
+                _ => continue,
+            };
+            let count = lowered.entry(address).or_insert(0);
+            *count += 1;
+            let floor = if *count > 8 { None } else { floor };
+            floors.insert(address, floor);
+
+            let after = match instruction.op {
+                Op::AdjustBase => floor.and_then(|f| Some(f + instruction.params[0].constant()?)),
+                _ => floor,
+            };
+            pending.extend(instruction.successors().into_iter().map(|s| (s, after)));
+            if let Some(target) = instruction.constant_result() {
+                if target >= 0 && self.entries.contains(&(target as usize)) {
+                    pending.push((target as usize, floor));
+                }
+            }
+        }
+        floors
+    }
+
+    fn find_accesses(&mut self) {
+        if self.instructions.values().all(|i| i.op != Op::AdjustBase) {
+            self.relative_base = Some(0);
+        }
+        let floors = self.base_floors();
+        for instruction in self.instructions.values() {
+            for (i, param) in instruction.params.iter().enumerate() {
+                let output = instruction.op.output() == Some(i);
+                match self.address(param) {
+                    Some(address) if output => {
+                        self.writes.insert(address);
+                    }
+                    Some(address) => {
+                        self.reads.insert(address);
+                    }
+                    None if param.mode == Mode::Relative && output => {
+                        let lowest = match floors.get(&instruction.address) {
+                            Some(Some(base)) => (base + param.value).max(0) as usize,
+                            _ => 0,
+                        };
+                        self.dynamic_writes =
+                            Some(self.dynamic_writes.map_or(lowest, |w| w.min(lowest)));
+                    }
+                    None if param.mode == Mode::Relative => self.dynamic_reads = true,
+                    None => {}
+                }
+            }
+        }
+        let leaders = self.leaders();
+        for instruction in self.instructions.values() {
+            for (i, param) in instruction.params.iter().enumerate() {
+                let operand = instruction.address + 1 + i;
+                if param.mode != Mode::Immediate && self.writes.contains(&operand) {
+                    if instruction.op.output() == Some(i) {
+                        let lowest = match param.mode {
+                            Mode::Position => self.table(instruction, operand, &leaders),
+                            _ => None,
+                        };
+                        let lowest = lowest.unwrap_or(0);
+                        self.dynamic_writes =
+                            Some(self.dynamic_writes.map_or(lowest, |w| w.min(lowest)));
+                    } else {
+                        self.dynamic_reads = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /* The start of the table a store indexes into, if its operand is only
+     * ever set by the instruction just before it adding a constant: that's
+     * taken to be the table's address, and the index to be positive.
+     */
+    fn table(
+        &self,
+        store: &Instruction,
+        operand: usize,
+        leaders: &BTreeSet<usize>,
+    ) -> Option<usize> {
+        if leaders.contains(&store.address) {
+            return None;
+        }
+        let (_, before) = self.instructions.range(..store.address).next_back()?;
+        if before.next() != store.address
+            || before.op != Op::Add
+            || self.address(&before.params[2]) != Some(operand)
+        {
+            return None;
+        }
+        let start = before.params[0]
+            .constant()
+            .or_else(|| before.params[1].constant())?;
+        if start >= 0 {
+            Some(start as usize)
+        } else {
+            None
+        }
+    }
+
+    /* The cell a parameter always addresses, if that's known statically. */
+    pub fn address(&self, param: &Param) -> Option<usize> {
+        let address = match param.mode {
+            Mode::Position => param.value,
+            Mode::Relative => self.relative_base? + param.value,
+            Mode::Immediate => return None,
+        };
+        if address >= 0 {
+            Some(address as usize)
+        } else {
+            None
+        }
+    }
+
+    /* The value a parameter always reads: its own if immediate, or what's
+     * in a cell nothing ever writes.
+     */
+    pub fn constant(&self, param: &Param) -> Option<i64> {
+        if let Some(value) = param.constant() {
+            return Some(value);
+        }
+        let address = self.address(param)?;
+        if self.may_be_written(address) {
+            return None;
+        }
+        Some(self.program.get(address).copied().unwrap_or(0))
+    }
+
+    /* Whether a cell could be changed while the program runs. */
+    pub fn may_be_written(&self, address: usize) -> bool {
+        self.writes.contains(&address) || self.dynamic_writes.is_some_and(|w| address >= w)
+    }
+
+    /* Whether any cell of the instruction could be changed while the
+     * program runs.
+     */
+    pub fn may_be_modified(&self, instruction: &Instruction) -> bool {
+        instruction.cells().any(|c| self.may_be_written(c))
+    }
+
+    /* Whether any cell of the instruction is read as data through a fixed
+     * address.  Reads through computed addresses are assumed to index data
+     * tables rather than code.
+     */
+    pub fn may_be_read(&self, instruction: &Instruction) -> bool {
+        instruction.cells().any(|c| self.reads.contains(&c))
+    }
+
+    /* Addresses where control may arrive other than by falling through. */
+    pub fn leaders(&self) -> BTreeSet<usize> {
+        let mut leaders = self.entries.clone();
+        for instruction in self.instructions.values() {
+            if instruction.is_jump() {
+                leaders.extend(instruction.successors());
+            }
+        }
+        leaders
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        let leaders = self.leaders();
+        let mut address = 0;
+        while address < self.program.len() {
+            match self.instructions.get(&address) {
+                Some(instruction) => {
+                    if leaders.contains(&address) {
+                        out += &format!("{}:\n", label(address));
+                    }
+                    out += &format!("{:>6}  {}\n", address, instruction);
+                    address = instruction.next();
+                }
+                None => {
+                    out += &format!("{:>6}  data {}\n", address, self.program[address]);
+                    address += 1;
+                }
+            }
+        }
+        out
+    }
+}
+
+pub fn label(address: usize) -> String {
+    format!("L{}", address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let i = Instruction::decode(&[1002, 4, 3, 4, 33], 0).unwrap();
+        assert_eq!(i.op, Op::Mul);
+        assert_eq!(
+            i.params,
+            vec![Param::position(4), Param::immediate(3), Param::position(4)]
+        );
+        assert_eq!(i.encode(), vec![1002, 4, 3, 4]);
+        assert_eq!(i.to_string(), "mul [4], 3, [4]");
+
+        assert_eq!(Instruction::decode(&[11101, 1, 2, 3], 0), None);
+        assert_eq!(Instruction::decode(&[42], 0), None);
+        assert_eq!(Instruction::decode(&[1, 2], 0), None);
+    }
+
+    #[test]
+    fn test_analysis() {
+        /* Calls a function at 11 which outputs 7 and returns via the stack. */
+        let program = vec![
+            109, 100, 21101, 9, 0, 0, 1105, 1, 11, 99, 0, 104, 7, 2106, 0, 0,
+        ];
+        let analysis = Analysis::new(&program);
+        assert_eq!(
+            analysis.instructions.keys().cloned().collect::<Vec<_>>(),
+            vec![0, 2, 6, 9, 11, 13]
+        );
+        assert!(analysis.entries.contains(&9));
+        assert!(analysis.instructions[&13].is_indirect());
+        /* The return address is pushed relative to a base which moves, but
+         * never below 100.
+         */
+        assert_eq!(analysis.dynamic_writes, Some(100));
+        assert!(analysis.writes.is_empty());
+        assert_eq!(analysis.relative_base, None);
+    }
+
+    #[test]
+    fn test_fixed_base() {
+        /* Nothing moves the base, so [rb + 7] is always cell 7. */
+        let program = vec![21101, 2, 3, 7, 204, 7, 1006, 8, 0, 99, 0];
+        let analysis = Analysis::new(&program);
+        assert_eq!(analysis.relative_base, Some(0));
+        assert_eq!(analysis.dynamic_writes, None);
+        assert!(analysis.writes.contains(&7));
+        assert!(analysis.reads.contains(&7));
+        assert_eq!(analysis.constant(&Param::position(7)), None);
+        assert_eq!(analysis.constant(&Param::position(8)), Some(0));
+        assert_eq!(analysis.constant(&Param::position(100)), Some(0));
+    }
+
+    #[test]
+    fn test_table() {
+        /* Stores 7 at 20 plus an input, by writing the store's operand. */
+        let program = vec![3, 13, 1001, 13, 20, 9, 1101, 7, 0, 0, 99, 0, 0, 0];
+        let analysis = Analysis::new(&program);
+        assert_eq!(analysis.dynamic_writes, Some(20));
+        assert!(!analysis.may_be_written(11));
+        assert!(analysis.may_be_written(20));
+    }
+
+    #[test]
+    fn test_self_modification() {
+        /* Stores 5 into the operand of the output instruction. */
+        let program = vec![1101, 2, 3, 5, 4, 0, 99];
+        let analysis = Analysis::new(&program);
+        assert!(analysis.writes.contains(&5));
+        assert!(analysis.may_be_modified(&analysis.instructions[&4]));
+        assert!(!analysis.may_be_modified(&analysis.instructions[&0]));
+        assert!(analysis.dynamic_reads);
+    }
+}
@@ -1,6 +1,6 @@
-use std::iter::Map;
-use std::io::{self, BufRead, Lines, Result};
 use std::cmp;
+use std::io::{self, BufRead, Lines, Result};
+use std::iter::Map;
 
 /* day 1 */
 fn fuel_for(mass: u32) -> u32 {
@@ -22,7 +22,7 @@ fn main() {
     let handle = stdin.lock();
     let l: Lines<_> = handle.lines();
     let lines: Map<_, _> = l.map(|l: Result<String>| l.unwrap());
-    let ints: _ = lines.map(|l| l.parse().unwrap());
+    let ints = lines.map(|l| l.parse().unwrap());
     let fuel: u32 = ints.map(fuel_for_rec).sum();
 
     println!("{}", fuel);
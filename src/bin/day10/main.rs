@@ -1,20 +1,10 @@
+use adventofcode2019::grid::Point;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::VecDeque;
-use std::fmt;
 use std::io::{self, BufRead};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Vector {
-    x: i32,
-    y: i32,
-}
-
-impl fmt::Display for Vector {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
+type Vector = Point<i32>;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Q {
@@ -22,10 +12,8 @@ struct Q {
     v: Vector,
 }
 
-impl Vector {
-    fn angle(&self) -> f64 {
-        (self.y as f64).atan2(self.x as f64)
-    }
+fn angle(v: &Vector) -> f64 {
+    (v.y as f64).atan2(v.x as f64)
 }
 
 fn risky_cmp<T>(a: &T, b: &T) -> Ordering
@@ -75,7 +63,7 @@ fn parse_map(lines: Vec<String>) -> Vec<Vector> {
     asteroids
 }
 
-fn visible_from(asteroids: &Vec<Vector>, i: usize) -> BTreeMap<Vector, Vec<Q>> {
+fn visible_from(asteroids: &[Vector], i: usize) -> BTreeMap<Vector, Vec<Q>> {
     let a = &asteroids[i];
     let mut equivs = BTreeMap::new();
     for (j, b) in asteroids.iter().enumerate() {
@@ -83,7 +71,7 @@ fn visible_from(asteroids: &Vec<Vector>, i: usize) -> BTreeMap<Vector, Vec<Q>> {
             let slope = get_slope(a, b);
             equivs
                 .entry(slope.v)
-                .or_insert_with(|| Vec::new())
+                .or_insert_with(Vec::new)
                 .push(Q { d: slope.d, v: *b });
         }
     }
@@ -93,21 +81,20 @@ fn visible_from(asteroids: &Vec<Vector>, i: usize) -> BTreeMap<Vector, Vec<Q>> {
     equivs
 }
 
-fn part1(asteroids: &Vec<Vector>) -> (BTreeMap<Vector, Vec<Q>>, usize) {
+fn part1(asteroids: &[Vector]) -> (BTreeMap<Vector, Vec<Q>>, usize) {
     (0..asteroids.len())
-        .map(|i| (visible_from(&asteroids, i), i))
+        .map(|i| (visible_from(asteroids, i), i))
         .max_by_key(|(v, _)| v.len())
         .unwrap()
 }
 
 fn part2(mut visible: BTreeMap<Vector, Vec<Q>>) -> Option<i32> {
-    let mut dirs: Vec<Vector> = visible.keys().map(|v| *v).collect();
-    dirs.sort_by(|a, b| risky_cmp(&a.angle(), &b.angle()));
+    let mut dirs: Vec<Vector> = visible.keys().copied().collect();
+    dirs.sort_by(|a, b| risky_cmp(&angle(a), &angle(b)));
     let (i, _) = dirs
         .iter()
         .enumerate()
-        .filter(|(_, v)| v.angle() >= (-std::f64::consts::PI / 2.))
-        .next()
+        .find(|(_, v)| angle(v) >= (-std::f64::consts::PI / 2.))
         .unwrap();
     let mut deque: VecDeque<&Vector> = dirs.iter().collect();
     deque.rotate_left(i);
@@ -0,0 +1,187 @@
+use adventofcode2019::grid::{Direction, Grid, Point, SparseGrid};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/* The robot painting a panel, then turning and moving on a panel. */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Paint {
+    pub at: Point,
+    pub colour: i64,
+    /* Which way it went afterwards. */
+    pub heading: Direction,
+}
+
+/* Everything the robot did, so it can be played back a step at a time. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    /* The colour of the panel it starts on. */
+    pub start: i64,
+    pub paints: Vec<Paint>,
+}
+
+impl History {
+    pub fn new(start: i64) -> Self {
+        Self {
+            start,
+            paints: Vec::new(),
+        }
+    }
+
+    /* The hull after the first steps paints. */
+    pub fn hull(&self, steps: usize) -> SparseGrid<i64> {
+        let mut hull = SparseGrid::new();
+        hull.insert(Point::default(), self.start);
+        for paint in &self.paints[..steps] {
+            hull.insert(paint.at, paint.colour);
+        }
+        hull
+    }
+
+    /* The hull once it's done. */
+    pub fn painted(&self) -> SparseGrid<i64> {
+        self.hull(self.paints.len())
+    }
+
+    /* Where the robot is after steps paints, and which way it faces. */
+    pub fn robot(&self, steps: usize) -> (Point, Direction) {
+        match steps.checked_sub(1).map(|i| self.paints[i]) {
+            Some(paint) => (paint.heading.advance(paint.at), paint.heading),
+            None => (Point::default(), Direction::N),
+        }
+    }
+
+    /* Every panel the robot stands on, in order. */
+    pub fn path(&self) -> Vec<Point> {
+        (0..=self.paints.len()).map(|i| self.robot(i).0).collect()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+fn heading(d: Direction) -> char {
+    match d {
+        Direction::N => 'N',
+        Direction::E => 'E',
+        Direction::S => 'S',
+        Direction::W => 'W',
+    }
+}
+
+/* A line with the starting colour, then a line per paint of x, y, colour
+ * and the heading afterwards.
+ */
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "start {}", self.start)?;
+        for paint in &self.paints {
+            let Paint { at, colour, .. } = paint;
+            writeln!(f, "{} {} {} {}", at.x, at.y, colour, heading(paint.heading))?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad line {:?}", line))
+}
+
+impl std::str::FromStr for History {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut lines = s.lines();
+        let first = lines.next().unwrap_or("");
+        let start = match first.split_whitespace().collect::<Vec<_>>()[..] {
+            ["start", colour] => colour.parse().map_err(|_| invalid(first))?,
+            _ => return Err(invalid(first)),
+        };
+        let mut history = Self::new(start);
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let paint = match words[..] {
+                [x, y, colour, d] => {
+                    let number = |w: &str| w.parse().map_err(|_| invalid(line));
+                    let heading = match d {
+                        "N" => Direction::N,
+                        "E" => Direction::E,
+                        "S" => Direction::S,
+                        "W" => Direction::W,
+                        _ => return Err(invalid(line)),
+                    };
+                    Paint {
+                        at: Point::new(number(x)?, number(y)?),
+                        colour: number(colour)?,
+                        heading,
+                    }
+                }
+                _ => return Err(invalid(line)),
+            };
+            history.paints.push(paint);
+        }
+        Ok(history)
+    }
+}
+
+/* Draws the hull with white panels as rockets, and the robot if given. */
+pub fn draw(hull: &SparseGrid<i64>, robot: Option<(Point, Direction)>) -> String {
+    let panels = hull.render(|p, c| match robot {
+        Some((at, d)) if at == p => match d {
+            Direction::N => '^',
+            Direction::E => '>',
+            Direction::S => 'v',
+            Direction::W => '<',
+        },
+        _ if c == Some(&1) => '🚀',
+        _ => ' ',
+    });
+    /* Rockets are twice as wide as everything else. */
+    panels
+        .chars()
+        .map(|c| match c {
+            '🚀' | '\n' => c.to_string(),
+            _ => format!("{} ", c),
+        })
+        .collect()
+}
+
+/* Replays the painting in place, fps steps a second. */
+pub fn animate(history: &History, fps: u32) {
+    let mut stdout = io::stdout();
+    /* Everywhere the robot goes is black until it's painted, and drawing
+     * it all from the start keeps the picture still.
+     */
+    let mut panels: SparseGrid<i64> = history.path().into_iter().map(|p| (p, 0)).collect();
+    let mut painted = BTreeSet::new();
+    print!("\x1b[2J\x1b[?25l");
+    for steps in 0..=history.paints.len() {
+        if steps > 0 {
+            let paint = history.paints[steps - 1];
+            panels.insert(paint.at, paint.colour);
+            painted.insert(paint.at);
+        } else {
+            panels.insert(Point::default(), history.start);
+        }
+        println!(
+            "\x1b[H{}\x1b[7m step {:>5} of {} | {} panels painted \x1b[0m\x1b[K",
+            draw(&panels, Some(history.robot(steps))),
+            steps,
+            history.paints.len(),
+            painted.len()
+        );
+        stdout.flush().unwrap();
+        thread::sleep(Duration::from_secs(1) / fps.max(1));
+    }
+    print!("\x1b[?25h");
+    stdout.flush().unwrap();
+}
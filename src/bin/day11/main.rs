@@ -1,109 +1,101 @@
 extern crate adventofcode2019;
 
-use adventofcode2019::{Memory, State, VM};
-use std::collections::BTreeMap;
-use std::fmt;
-use std::io::{self, BufRead};
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Point {
-    x: i32,
-    y: i32,
-}
+mod history;
 
-impl fmt::Display for Point {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
+use adventofcode2019::grid::{DenseGrid, Direction, Grid, Point, SparseGrid};
+use adventofcode2019::image::{self, Frame};
+use adventofcode2019::ocr;
+use adventofcode2019::{Memory, VM};
+use history::{animate as animate_history, History, Paint};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter};
 
-#[derive(Debug, PartialEq, Eq)]
-enum Direction {
-    N,
-    E,
-    S,
-    W,
-}
+/* Runs the robot, starting on a panel of the given colour. */
+fn paint(program: &Memory, start: i64) -> History {
+    let mut vm = VM::new(program);
+    let history = RefCell::new(History::new(start));
+    let colours = RefCell::new(SparseGrid::new());
+    let point = Cell::new(Point::default());
+    let mut direction = Direction::N;
 
-impl Direction {
-    fn left(self) -> Self {
-        match self {
-            Direction::N => Direction::E,
-            Direction::E => Direction::S,
-            Direction::S => Direction::W,
-            Direction::W => Direction::N,
-        }
-    }
-    fn right(self) -> Self {
-        match self {
-            Direction::N => Direction::W,
-            Direction::E => Direction::N,
-            Direction::S => Direction::E,
-            Direction::W => Direction::S,
-        }
-    }
+    colours.borrow_mut().insert(point.get(), start);
 
-    fn advance(&self, p: Point) -> Point {
-        match self {
-            Direction::N => Point { x: p.x, y: p.y + 1 },
-            Direction::E => Point { x: p.x + 1, y: p.y },
-            Direction::S => Point { x: p.x, y: p.y - 1 },
-            Direction::W => Point { x: p.x - 1, y: p.y },
-        }
+    let camera = |_: &VM| Some(*colours.borrow().get(&point.get()).unwrap_or(&0));
+    for [colour, turn] in vm.outputs(camera).groups() {
+        colours.borrow_mut().insert(point.get(), colour);
+        direction = match turn {
+            0 => direction.left(),
+            1 => direction.right(),
+            _ => panic!("{}", turn),
+        };
+        history.borrow_mut().paints.push(Paint {
+            at: point.get(),
+            colour,
+            heading: direction,
+        });
+        point.set(direction.advance(point.get()));
     }
-}
 
-fn paint(program: &Memory, start: i64) -> BTreeMap<Point, i64> {
-    let mut vm = VM::new(program);
-    let mut colours: BTreeMap<Point, i64> = BTreeMap::new();
-    let mut point = Point { x: 0, y: 0 };
-    let mut direction = Direction::N;
+    history.into_inner()
+}
 
-    colours.insert(point, start);
-
-    loop {
-        vm.input.push_back(*colours.get(&point).unwrap_or(&0));
-        match vm.run() {
-            State::Halted => break,
-            State::NeedInput => {
-                let colour = vm.output.pop_front().unwrap();
-                let turn = vm.output.pop_front().unwrap();
-                // println!("{} {}", colour, turn,);
-                colours.insert(point, colour);
-                direction = match turn {
-                    0 => direction.left(),
-                    1 => direction.right(),
-                    _ => panic!("{}", turn),
-                };
-                point = direction.advance(point);
-            }
-        }
+/* White panels are 1s in the picture. */
+fn picture(hull: &SparseGrid<i64>) -> Frame {
+    let bounds = hull.bounds().expect("the robot paints where it starts");
+    let mut frame = Frame::new(bounds.width() as usize, bounds.height() as usize);
+    for (&p, &colour) in hull {
+        let at = p - bounds.min;
+        frame.set(at.x as usize, at.y as usize, colour as u8);
     }
+    frame
+}
 
-    colours
+/* The letters the robot painted. */
+fn read(hull: &SparseGrid<i64>) -> Result<String, ocr::Unrecognised> {
+    let bounds = hull.bounds().expect("the robot paints where it starts");
+    let rows = bounds
+        .rows()
+        .map(|row| row.map(|p| hull.get(&p) == Some(&1)).collect());
+    ocr::read(&DenseGrid::from_rows(rows.collect()))
 }
 
-fn render(hull: BTreeMap<Point, i64>) {
-    let pmin = Point {
-        x: hull.keys().map(|p| p.x).min().unwrap(),
-        y: hull.keys().map(|p| p.y).min().unwrap(),
-    };
-    let pmax = Point {
-        x: hull.keys().map(|p| p.x).max().unwrap(),
-        y: hull.keys().map(|p| p.y).max().unwrap(),
-    };
-
-    for y in (pmin.y..(pmax.y + 1)).rev() {
-        let row = (pmin.x..(pmax.x + 1))
-            .rev()
-            .map(|x| *hull.get(&Point { x, y }).unwrap_or(&0))
-            .map(|c| if c == 0 { "  " } else { "🚀" })
-            .collect::<String>();
-        println!("{}", row);
+/* As a PNG if the path ends in .png, and a PBM otherwise. */
+fn export(hull: &SparseGrid<i64>, path: &str, scale: usize) -> io::Result<()> {
+    let frame = picture(hull).scaled(scale);
+    let out = BufWriter::new(File::create(path)?);
+    if path.ends_with(".png") {
+        image::write_png(out, &frame, &[[0, 0, 0], [255, 255, 255]])
+    } else {
+        image::write_pbm(out, &frame)
     }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = args.iter().map(String::as_str);
+    let (mut export_to, mut log, mut play) = (None, None, None);
+    let (mut animate, mut fps, mut scale) = (false, 60, 4);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--export" => export_to = Some(args.next().expect("--export PATH")),
+            "--scale" => scale = args.next().and_then(|n| n.parse().ok()).expect("--scale N"),
+            "--log" => log = Some(args.next().expect("--log PATH")),
+            "--play" => play = Some(args.next().expect("--play PATH")),
+            "--animate" => animate = true,
+            "--fps" => fps = args.next().and_then(|n| n.parse().ok()).expect("--fps N"),
+            _ => panic!(
+                "usage: [--export PATH.pbm|PATH.png] [--scale N] [--log PATH] \
+                 [--animate] [--play PATH] [--fps N]"
+            ),
+        }
+    }
+    if let Some(path) = play {
+        animate_history(&History::load(path).expect(path), fps);
+        return;
+    }
+
     let stdin = io::stdin();
     let handle = stdin.lock();
     let line = handle
@@ -113,9 +105,72 @@ fn main() {
         .join("");
     let program: Memory = line.split(",").map(|s| s.parse().unwrap()).collect();
 
-    let part1 = paint(&program, 0);
+    let part1 = paint(&program, 0).painted();
     println!("{}", part1.len());
-    render(part1);
-    let part2 = paint(&program, 1);
-    render(part2);
+    print!("{}", history::draw(&part1, None));
+    /* The rest is about part 2, which paints something worth seeing. */
+    let history = paint(&program, 1);
+    let part2 = history.painted();
+    print!("{}", history::draw(&part2, None));
+    match read(&part2) {
+        Ok(text) => println!("{}", text),
+        Err(e) => println!("{}", e),
+    }
+    if let Some(path) = export_to {
+        export(&part2, path, scale).expect(path);
+    }
+    if let Some(path) = log {
+        history.save(path).expect(path);
+    }
+    if animate {
+        animate_history(&history, fps);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::data_program;
+
+    fn program() -> Memory {
+        data_program("day11-input")
+    }
+
+    #[test]
+    fn test_history() {
+        let program = program();
+        assert_eq!(paint(&program, 0).painted().len(), 2594);
+
+        let history = paint(&program, 1);
+        let parsed: History = history.to_string().parse().unwrap();
+        assert_eq!(parsed, history);
+        assert!("start 1\n0 0 1 Q\n".parse::<History>().is_err());
+
+        /* Replaying a step at a time ends up where the robot did. */
+        let mut hull = history.hull(0);
+        for (i, paint) in history.paints.iter().enumerate() {
+            assert_eq!(history.robot(i).0, paint.at);
+            hull.insert(paint.at, paint.colour);
+            assert_eq!(hull, history.hull(i + 1));
+        }
+        assert_eq!(history.path().len(), history.paints.len() + 1);
+    }
+
+    #[test]
+    fn test_picture() {
+        let frame = picture(&paint(&program(), 1).painted());
+        assert_eq!((frame.width, frame.height), (43, 6));
+        /* The first letter, after a column of unpainted panels, is an A the
+         * right way round.
+         */
+        let a: Vec<String> = (0..6)
+            .map(|y| {
+                (1..5)
+                    .map(|x| if frame.get(x, y) == 1 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(a, [".##.", "#..#", "#..#", "####", "#..#", "#..#"]);
+        assert_eq!(read(&paint(&program(), 1).painted()).unwrap(), "AKERJFHK");
+    }
 }
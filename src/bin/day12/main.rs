@@ -72,9 +72,9 @@ fn step(moons: &mut Vec<Moon>) {
     for i in 0..moons.len() {
         let m = moons[i].pos;
         let mut delta_v = V3::zero();
-        for j in 0..moons.len() {
+        for (j, n) in moons.iter().enumerate() {
             if j != i {
-                let n = moons[j].pos;
+                let n = n.pos;
                 delta_v += V3 {
                     x: (n.x - m.x).signum(),
                     y: (n.y - m.y).signum(),
@@ -89,12 +89,12 @@ fn step(moons: &mut Vec<Moon>) {
     }
 }
 
-fn energy(moons: &Vec<Moon>) -> i64 {
+fn energy(moons: &[Moon]) -> i64 {
     moons.iter().map(|m| m.energy()).sum()
 }
 
 fn parse_line(line: &str) -> Moon {
-    let mut bits = (&line[1..line.len() - 1])
+    let mut bits = line[1..line.len() - 1]
         .split(",")
         .map(|s: &str| (s.trim()).get(2..).expect("oh no").parse().expect("OH NO"));
     let x = bits.next().expect("x");
@@ -128,10 +128,9 @@ fn step_1d(moons: &mut Vec<Moon1D>) {
     for i in 0..moons.len() {
         let m = moons[i].pos;
         let mut delta_v = 0;
-        for j in 0..moons.len() {
+        for (j, n) in moons.iter().enumerate() {
             if j != i {
-                let n = moons[j].pos;
-                delta_v += (n - m).signum();
+                delta_v += (n.pos - m).signum();
             }
         }
         moons[i].vel += delta_v;
@@ -170,7 +169,7 @@ fn main() {
         .map(|l| l.unwrap())
         .map(|s| parse_line(&s))
         .collect();
-    let mut part1 = moons.iter().copied().collect();
+    let mut part1 = moons.to_vec();
     for _ in 0..1000 {
         step(&mut part1);
     }
@@ -0,0 +1,103 @@
+use crate::Game;
+use adventofcode2019::image::{self, Frame, Gif, Rgb};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+
+/* Empty, wall, block, paddle and ball. */
+pub const PALETTE: [Rgb; 5] = [
+    [0x10, 0x10, 0x20],
+    [0x80, 0x80, 0x90],
+    [0xd0, 0x40, 0x30],
+    [0xf0, 0xf0, 0xf0],
+    [0xf0, 0xd0, 0x20],
+];
+
+enum Target {
+    Gif(Gif<BufWriter<File>>),
+    /* A directory of frame-00000.ppm, frame-00001.ppm, ... */
+    Ppm(PathBuf),
+}
+
+/* Saves each frame of the game which looks different from the last. */
+pub struct Exporter {
+    path: PathBuf,
+    palette: Vec<Rgb>,
+    scale: usize,
+    /* Hundredths of a second per frame, for GIFs. */
+    delay: u16,
+    target: Option<Target>,
+    last: Option<Frame>,
+    frames: usize,
+}
+
+impl Exporter {
+    /* Writes a GIF if the path ends in .gif, and PPMs into it otherwise. */
+    pub fn new(path: &str, palette: &[Rgb], scale: usize, fps: u32) -> Self {
+        assert_eq!(palette.len(), PALETTE.len(), "one colour for each tile");
+        Self {
+            path: PathBuf::from(path),
+            palette: palette.to_vec(),
+            scale,
+            delay: (100 / fps.max(1)).max(2) as u16,
+            target: None,
+            last: None,
+            frames: 0,
+        }
+    }
+
+    fn frame(&self, game: &Game) -> Frame {
+        let mut frame = Frame::new(game.width(), game.height());
+        for (y, row) in game.rows().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                frame.set(x, y, tile as u8);
+            }
+        }
+        frame.scaled(self.scale)
+    }
+
+    /* The GIF's size is fixed by the first frame, so it's only created
+     * once there's something to draw.
+     */
+    fn open(&self, frame: &Frame) -> io::Result<Target> {
+        if self.path.extension().is_some_and(|e| e == "gif") {
+            let out = BufWriter::new(File::create(&self.path)?);
+            let gif = Gif::new(out, frame.width, frame.height, &self.palette, self.delay)?;
+            Ok(Target::Gif(gif))
+        } else {
+            fs::create_dir_all(&self.path)?;
+            Ok(Target::Ppm(self.path.clone()))
+        }
+    }
+
+    pub fn capture(&mut self, game: &Game) -> io::Result<()> {
+        if game.height() == 0 {
+            return Ok(());
+        }
+        let frame = self.frame(game);
+        if self.last.as_ref() == Some(&frame) {
+            return Ok(());
+        }
+        if self.target.is_none() {
+            self.target = Some(self.open(&frame)?);
+        }
+        match self.target.as_mut().unwrap() {
+            Target::Gif(gif) => gif.add(&frame)?,
+            Target::Ppm(dir) => {
+                let path = dir.join(format!("frame-{:05}.ppm", self.frames));
+                image::write_ppm(BufWriter::new(File::create(path)?), &frame, &self.palette)?;
+            }
+        }
+        self.frames += 1;
+        self.last = Some(frame);
+        Ok(())
+    }
+
+    /* Returns how many frames were written. */
+    pub fn finish(self) -> io::Result<usize> {
+        if let Some(Target::Gif(gif)) = self.target {
+            gif.finish()?;
+        }
+        Ok(self.frames)
+    }
+}
@@ -1,55 +1,114 @@
 extern crate adventofcode2019;
 
-use adventofcode2019::{program_from_stdin, Memory, State, VM};
+mod export;
+mod strategy;
+mod terminal;
+
+use adventofcode2019::grid::{DenseGrid, Grid, Point};
+use adventofcode2019::image::parse_rgb;
+use adventofcode2019::replay::{Replay, Session};
+use adventofcode2019::{program_from_stdin, State, VM};
+use export::Exporter;
 use itertools::Itertools;
-use std::collections::BTreeMap;
-use std::fmt;
-use std::io::{self, BufRead};
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Point {
-    x: i64,
-    y: i64,
-}
+use std::cell::RefCell;
+use std::env;
+use strategy::Strategy;
+use terminal::{Animation, Keyboard};
 
-impl fmt::Display for Point {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
+pub const WALL: i64 = 1;
+pub const BLOCK: i64 = 2;
+pub const PADDLE: i64 = 3;
+pub const BALL: i64 = 4;
 
-struct Game {
-    grid: [[i64; 35]; 25],
-    paddle: Point,
-    ball_dir: i64,
-    ball: Point,
-    score: i64,
+#[derive(Clone)]
+pub struct Game {
+    /* Grown to fit whatever is drawn, doubling so that drawing the screen a
+     * tile at a time only copies it a few times.  Only the first width by
+     * height cells have been drawn on.
+     */
+    grid: DenseGrid<i64>,
+    width: usize,
+    height: usize,
+    pub paddle: Option<Point>,
+    pub ball: Option<Point>,
+    /* How far the ball moved when it was last drawn. */
+    pub velocity: Option<Point>,
+    pub score: i64,
 }
+
 impl Game {
     fn new() -> Self {
         Self {
-            grid: [[0; 35]; 25],
-            paddle: Point { x: 0, y: 0 },
-            ball_dir: 0,
-            ball: Point { x: 0, y: 0 },
+            grid: DenseGrid::new(0, 0, 0),
+            width: 0,
+            height: 0,
+            paddle: None,
+            ball: None,
+            velocity: None,
             score: 0,
         }
     }
 
-    fn set(&mut self, x: i64, y: i64, cell: i64) {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /* Off the screen is empty. */
+    pub fn get(&self, p: Point) -> i64 {
+        self.grid.get(p).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, cell: i64) {
         if x == -1 && y == 0 {
             self.score = cell;
-        } else {
-            self.grid[y as usize][x as usize] = cell;
-            if (cell == 3) {
-                self.paddle = Point { x, y };
-            } else if (cell == 4) {
-                self.ball_dir = (x - self.ball.x).signum();
-                self.ball = Point { x, y };
+            return;
+        }
+        let (column, row) = (x as usize, y as usize);
+        self.width = self.width.max(column + 1);
+        self.height = self.height.max(row + 1);
+        if self.width > self.grid.width() || self.height > self.grid.height() {
+            let grow = |have: usize, need: usize| {
+                if need > have {
+                    need.max(2 * have)
+                } else {
+                    have
+                }
+            };
+            let width = grow(self.grid.width(), self.width);
+            let height = grow(self.grid.height(), self.height);
+            self.grid.resize(width, height, 0);
+        }
+        let p = Point { x, y };
+        self.grid.set(p, cell);
+        if cell == PADDLE {
+            self.paddle = Some(p);
+        } else if cell == BALL {
+            if let Some(ball) = self.ball {
+                self.velocity = Some(Point {
+                    x: x - ball.x,
+                    y: y - ball.y,
+                });
             }
+            self.ball = Some(p);
         }
     }
 
+    pub fn rows(&self) -> impl Iterator<Item = &[i64]> {
+        let width = self.width;
+        self.grid
+            .rows()
+            .take(self.height)
+            .map(move |row| &row[..width])
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.grid.cells().filter(|&&c| c == BLOCK).count()
+    }
+
     fn render_cell(cell: i64) -> char {
         match cell {
             0 => ' ',
@@ -61,16 +120,17 @@ impl Game {
         }
     }
 
+    pub fn lines(&self) -> Vec<String> {
+        self.rows()
+            .map(|row| row.iter().map(|&cell| Self::render_cell(cell)).collect())
+            .collect()
+    }
+
     fn render(&self) {
-        for row in &self.grid {
-            println!(
-                "{}",
-                row.iter()
-                    .map(|&cell| Self::render_cell(cell))
-                    .collect::<String>()
-            );
+        for line in self.lines() {
+            println!("{}", line);
         }
-        println!("{:^36}", self.score);
+        println!("{:^w$}", self.score, w = self.width() + 1);
     }
 
     fn update(&mut self, vm: &mut VM) {
@@ -79,22 +139,235 @@ impl Game {
                 self.set(x, y, cell);
             }
         }
-        self.render();
     }
 }
 
+/* How the game is shown as it's played. */
+enum Screen {
+    Headless,
+    /* Every frame printed after the last. */
+    Scroll,
+    Animate(Animation),
+}
+
+impl Screen {
+    fn frame(&mut self, game: &Game, steps: u64) {
+        match self {
+            Screen::Headless => {}
+            Screen::Scroll => {
+                game.render();
+                println!();
+            }
+            Screen::Animate(animation) => animation.draw(game, steps),
+        }
+    }
+}
+
+/* Plays until the game ends, or the player gives up, passing each frame to
+ * show().  Returns the final state of the game, and how many blocks there
+ * were at the start.
+ */
+fn play(
+    vm: &mut VM,
+    strategy: &mut dyn Strategy,
+    show: &mut dyn FnMut(&Game, u64),
+) -> (Game, usize) {
+    let game = RefCell::new(Game::new());
+    let mut blocks = None;
+    let joystick = |vm: &VM| {
+        let game = game.borrow();
+        blocks.get_or_insert_with(|| game.blocks());
+        show(&game, vm.steps());
+        strategy.joystick(&game)
+    };
+    for [x, y, cell] in vm.outputs(joystick).groups() {
+        game.borrow_mut().set(x, y, cell);
+    }
+    let game = game.into_inner();
+    show(&game, vm.steps());
+    let blocks = blocks.unwrap_or_else(|| game.blocks());
+    (game, blocks)
+}
+
 fn main() {
     let mut program = program_from_stdin();
     program[0] = 2;
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = args.iter().map(String::as_str);
+    let (mut headless, mut animate, mut manual) = (false, false, false);
+    let mut fps = 30;
+    let mut strategy = "follow";
+    let (mut export, mut scale) = (None, 4);
+    let mut palette = export::PALETTE.to_vec();
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--headless" => headless = true,
+            "--animate" => animate = true,
+            "--manual" => manual = true,
+            "--fps" => fps = args.next().and_then(|n| n.parse().ok()).expect("--fps N"),
+            "--strategy" => strategy = args.next().expect("--strategy NAME"),
+            "--export" => export = Some(args.next().expect("--export PATH")),
+            "--scale" => scale = args.next().and_then(|n| n.parse().ok()).expect("--scale N"),
+            "--palette" => {
+                palette = args
+                    .next()
+                    .and_then(|p| p.split(',').map(parse_rgb).collect())
+                    .filter(|p: &Vec<_>| p.len() == export::PALETTE.len())
+                    .expect("--palette EMPTY,WALL,BLOCK,PADDLE,BALL as hex colours")
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let mut screen = if headless {
+        Screen::Headless
+    } else if animate || manual {
+        Screen::Animate(Animation::new(fps))
+    } else {
+        Screen::Scroll
+    };
+    let mut exporter = export.map(|path| Exporter::new(path, &palette, scale, fps));
+    let mut show = |game: &Game, steps: u64| {
+        screen.frame(game, steps);
+        if let Some(exporter) = &mut exporter {
+            exporter.capture(game).expect("export");
+        }
+    };
+
     let mut vm = VM::new(&program);
-    let mut game = Game::new();
-    while let State::NeedInput = vm.run() {
-        game.update(&mut vm);
+    let session = Session::parse(&rest);
+    if let Session::Replay(recording) = session {
+        let mut game = Game::new();
+        let mut replay = Replay::new(recording);
+        loop {
+            let state = replay.resume(&mut vm).unwrap_or_else(|d| panic!("{}", d));
+            game.update(&mut vm);
+            show(&game, vm.steps());
+            if let State::Halted = state {
+                break;
+            }
+            if replay.finished() {
+                println!("end of recording");
+                break;
+            }
+        }
+    } else {
+        session.start(&mut vm);
+        let (game, blocks) = if manual {
+            play(&mut vm, &mut Keyboard::new(), &mut show)
+        } else {
+            play(&mut vm, strategy::by_name(strategy).as_mut(), &mut show)
+        };
+        session.finish(&mut vm);
+        if headless {
+            println!("{}", blocks);
+            println!("{}", game.score);
+        }
+    }
+    if let Some(exporter) = exporter {
+        let frames = exporter.finish().expect("export");
+        eprintln!("exported {} frames", frames);
+    }
+}
 
-        let joystick = (game.ball.x - game.paddle.x).signum();
-        vm.input.push_back(joystick);
-        println!("");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::data_program;
+    use std::fs;
+
+    fn arcade() -> VM {
+        let mut program = data_program("day13-input");
+        program[0] = 2;
+        VM::new(&program)
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut game = Game::new();
+        game.set(2, 1, BLOCK);
+        assert_eq!((game.width(), game.height()), (3, 2));
+        game.set(4, 0, WALL);
+        assert_eq!((game.width(), game.height()), (5, 2));
+        assert_eq!(game.get(Point { x: 4, y: 1 }), 0);
+        assert_eq!(game.get(Point { x: -1, y: 7 }), 0);
+        game.set(-1, 0, 12);
+        assert_eq!((game.score, game.blocks()), (12, 1));
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = env::temp_dir().join(format!("day13-export-{}", std::process::id()));
+        let mut exporter = Exporter::new(dir.to_str().unwrap(), &export::PALETTE, 2, 30);
+        let mut game = Game::new();
+        exporter.capture(&game).unwrap();
+        game.set(1, 0, WALL);
+        exporter.capture(&game).unwrap();
+        exporter.capture(&game).unwrap();
+        /* The score isn't drawn. */
+        game.set(-1, 0, 100);
+        exporter.capture(&game).unwrap();
+        game.set(0, 0, BALL);
+        exporter.capture(&game).unwrap();
+        assert_eq!(exporter.finish().unwrap(), 2);
+        let first = fs::read(dir.join("frame-00000.ppm")).unwrap();
+        assert_eq!(&first[..11], b"P6\n4 2\n255\n");
+        assert_eq!(&first[11..17], &[0x10, 0x10, 0x20, 0x10, 0x10, 0x20]);
+        assert_eq!(&first[17..20], &export::PALETTE[WALL as usize]);
+        assert!(dir.join("frame-00001.ppm").exists());
+        fs::remove_dir_all(dir).unwrap();
+
+        let path = env::temp_dir().join(format!("day13-{}.gif", std::process::id()));
+        let mut exporter = Exporter::new(path.to_str().unwrap(), &export::PALETTE, 1, 30);
+        exporter.capture(&game).unwrap();
+        assert_eq!(exporter.finish().unwrap(), 1);
+        let gif = fs::read(&path).unwrap();
+        assert_eq!(&gif[..10], b"GIF89a\x02\x00\x01\x00");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_strategies() {
+        for name in &["follow", "predict"] {
+            let mut vm = arcade();
+            let (game, blocks) = play(&mut vm, strategy::by_name(name).as_mut(), &mut |_, _| {});
+            assert_eq!(
+                (blocks, game.score, game.blocks()),
+                (309, 15410, 0),
+                "{}",
+                name
+            );
+            assert_eq!((game.width(), game.height()), (35, 25));
+        }
+    }
+
+    #[test]
+    fn test_prediction() {
+        /* Every prediction made while the ball is on its way is where it
+         * then comes down.
+         */
+        let mut vm = arcade();
+        let game = RefCell::new(Game::new());
+        let mut predictions = Vec::new();
+        let mut landings = 0;
+        let joystick = |_: &VM| {
+            let game = game.borrow();
+            let (ball, paddle) = (game.ball.unwrap(), game.paddle.unwrap());
+            let falling = game.velocity.is_some_and(|v| v.y > 0);
+            if falling && ball.y + 1 == paddle.y {
+                for x in predictions.drain(..) {
+                    assert_eq!(x, Some(ball.x));
+                }
+                landings += 1;
+            } else if game.velocity.is_some() {
+                predictions.push(strategy::PredictLanding::landing(&game));
+            }
+            strategy::FollowBall.joystick(&game)
+        };
+        for [x, y, cell] in vm.outputs(joystick).groups() {
+            game.borrow_mut().set(x, y, cell);
+        }
+        assert!(landings > 100);
     }
-    game.update(&mut vm);
 }
@@ -0,0 +1,102 @@
+use crate::{Game, BLOCK, WALL};
+use adventofcode2019::grid::Point;
+
+/* Decides which way to push the joystick: -1 for left, 0 to stay, 1 for
+ * right, or None to stop playing.
+ */
+pub trait Strategy {
+    fn joystick(&mut self, game: &Game) -> Option<i64>;
+}
+
+fn towards(paddle: Option<Point>, x: Option<i64>) -> Option<i64> {
+    match (paddle, x) {
+        (Some(paddle), Some(x)) => Some((x - paddle.x).signum()),
+        _ => Some(0),
+    }
+}
+
+/* Keeps the paddle under the ball. */
+pub struct FollowBall;
+
+impl Strategy for FollowBall {
+    fn joystick(&mut self, game: &Game) -> Option<i64> {
+        towards(game.paddle, game.ball.map(|b| b.x))
+    }
+}
+
+/* Heads for where the ball will come down, so the paddle is waiting there
+ * rather than chasing it.
+ */
+pub struct PredictLanding;
+
+impl PredictLanding {
+    /* Plays the ball forward, bouncing off walls and knocking out blocks,
+     * until it reaches the paddle's row.
+     */
+    pub fn landing(game: &Game) -> Option<i64> {
+        let paddle = game.paddle?;
+        let mut ball = game.ball?;
+        let mut velocity = game.velocity?;
+        let mut grid = game.clone();
+        /* Whether the ball bounces off p, knocking it out if it's a block. */
+        let mut bounce = |p: Point| match grid.get(p) {
+            WALL => true,
+            BLOCK => {
+                grid.set(p.x, p.y, 0);
+                true
+            }
+            _ => false,
+        };
+
+        for _ in 0..game.width() * game.height() * 4 {
+            if velocity.y > 0 && ball.y + 1 >= paddle.y {
+                return Some(ball.x);
+            }
+            let horizontal = Point {
+                x: ball.x + velocity.x,
+                y: ball.y,
+            };
+            let vertical = Point {
+                x: ball.x,
+                y: ball.y + velocity.y,
+            };
+            let corner = Point {
+                x: ball.x + velocity.x,
+                y: ball.y + velocity.y,
+            };
+            let mut bounced = false;
+            if bounce(horizontal) {
+                velocity.x = -velocity.x;
+                bounced = true;
+            }
+            if bounce(vertical) {
+                velocity.y = -velocity.y;
+                bounced = true;
+            }
+            if !bounced && bounce(corner) {
+                velocity.x = -velocity.x;
+                velocity.y = -velocity.y;
+                bounced = true;
+            }
+            if !bounced {
+                ball = corner;
+            }
+        }
+        None
+    }
+}
+
+impl Strategy for PredictLanding {
+    fn joystick(&mut self, game: &Game) -> Option<i64> {
+        let target = Self::landing(game).or_else(|| game.ball.map(|b| b.x));
+        towards(game.paddle, target)
+    }
+}
+
+pub fn by_name(name: &str) -> Box<dyn Strategy> {
+    match name {
+        "follow" => Box::new(FollowBall),
+        "predict" => Box::new(PredictLanding),
+        _ => panic!("unknown strategy {} (try follow or predict)", name),
+    }
+}
@@ -0,0 +1,151 @@
+use crate::strategy::Strategy;
+use crate::Game;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/* Redraws the game in place, at most fps times a second. */
+pub struct Animation {
+    frame: Duration,
+    next: Option<Instant>,
+}
+
+impl Animation {
+    pub fn new(fps: u32) -> Self {
+        Self {
+            frame: Duration::from_secs(1) / fps.max(1),
+            next: None,
+        }
+    }
+
+    pub fn draw(&mut self, game: &Game, steps: u64) {
+        let mut s = String::new();
+        if self.next.is_none() {
+            /* Clear the screen and hide the cursor. */
+            s.push_str("\x1b[2J\x1b[?25l");
+        }
+        s.push_str("\x1b[H");
+        for line in game.lines() {
+            s.push_str(&line);
+            s.push_str("\r\n");
+        }
+        s.push_str(&format!(
+            "\x1b[7m score {:>6} | blocks {:>4} | steps {:>9} \x1b[0m\x1b[K\r\n",
+            game.score,
+            game.blocks(),
+            steps
+        ));
+        let mut stdout = io::stdout();
+        stdout.write_all(s.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+
+        let now = Instant::now();
+        let next = self.next.unwrap_or(now);
+        if next > now {
+            thread::sleep(next - now);
+        }
+        self.next = Some(next.max(now) + self.frame);
+    }
+}
+
+impl Drop for Animation {
+    fn drop(&mut self) {
+        print!("\x1b[?25h");
+        io::stdout().flush().unwrap();
+    }
+}
+
+fn stty(args: &[&str]) -> String {
+    let tty = File::open("/dev/tty").expect("/dev/tty");
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty))
+        .output()
+        .expect("stty");
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/* Puts the terminal in raw mode, so keys arrive as soon as they're pressed,
+ * and puts it back afterwards.
+ */
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enter() -> Self {
+        let saved = stty(&["-g"]);
+        stty(&["raw", "-echo"]);
+        Self { saved }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Right,
+    Quit,
+}
+
+/* Keys from the terminal, read on a thread of their own.  The arrow keys,
+ * a and d, or h and l steer; q, Escape or ^C quit.
+ */
+fn keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    let mut tty = File::open("/dev/tty").expect("/dev/tty");
+    thread::spawn(move || {
+        let mut buffer = [0; 16];
+        while let Ok(n) = tty.read(&mut buffer) {
+            let key = match &buffer[..n] {
+                b"\x1b[D" | b"a" | b"h" => Key::Left,
+                b"\x1b[C" | b"d" | b"l" => Key::Right,
+                b"q" | b"\x1b" | b"\x03" => Key::Quit,
+                _ => continue,
+            };
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/* The player at the keyboard: the paddle moves in the direction of the
+ * last key pressed since the previous frame, or stays put.
+ */
+pub struct Keyboard {
+    keys: Receiver<Key>,
+    _raw: RawMode,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self {
+            _raw: RawMode::enter(),
+            keys: keys(),
+        }
+    }
+}
+
+impl Strategy for Keyboard {
+    fn joystick(&mut self, _game: &Game) -> Option<i64> {
+        let mut joystick = 0;
+        for key in self.keys.try_iter() {
+            joystick = match key {
+                Key::Left => -1,
+                Key::Right => 1,
+                Key::Quit => return None,
+            };
+        }
+        Some(joystick)
+    }
+}
@@ -1,9 +1,6 @@
-use adventofcode2019::lcm;
 use num_rational::Ratio;
-use std::collections::{BTreeMap, HashMap};
-use std::fmt;
+use std::collections::HashMap;
 use std::io::{self, BufRead};
-use std::ops::{Add, AddAssign};
 
 type Q = Ratio<usize>;
 
@@ -14,10 +11,10 @@ fn parse_chem(s: &str) -> (&str, Q) {
     (chemical, quantity)
 }
 
-fn parse_input<'a>(
-    lines: &'a Vec<std::string::String>,
-) -> HashMap<&'a str, (Q, Vec<(&'a str, Q)>)> {
-    let mut reactions: HashMap<&str, (Q, Vec<(&str, Q)>)> = HashMap::new();
+type Reactions<'a> = HashMap<&'a str, (Q, Vec<(&'a str, Q)>)>;
+
+fn parse_input(lines: &[String]) -> Reactions<'_> {
+    let mut reactions: Reactions = HashMap::new();
     for line in lines {
         let bits: Vec<_> = line.split(" => ").collect();
         let inputs: Vec<_> = bits[0].split(", ").map(parse_chem).collect();
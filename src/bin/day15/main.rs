@@ -1,49 +1,23 @@
 extern crate adventofcode2019;
 
-use adventofcode2019::{program_from_stdin, State, VM};
-use std::collections::BTreeMap;
-use std::fmt;
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Point {
-    x: i64,
-    y: i64,
-}
-
-impl fmt::Display for Point {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Direction {
-    N = 1,
-    E = 4,
-    S = 2,
-    W = 3,
-}
-
-impl Direction {
-    fn advance(&self, p: Point) -> Point {
-        match self {
-            Direction::N => Point { x: p.x, y: p.y + 1 },
-            Direction::E => Point { x: p.x + 1, y: p.y },
-            Direction::S => Point { x: p.x, y: p.y - 1 },
-            Direction::W => Point { x: p.x - 1, y: p.y },
-        }
-    }
+mod map;
+mod oxygen;
 
-    /* Stack of moves after successful move in direction of self.
-     * Backwards is at the bottom.
-     */
-    fn succs(&self) -> (Self, Vec<Self>) {
-        match self {
-            Self::N => (Self::S, vec![Self::W, Self::E, Self::N]),
-            Self::E => (Self::W, vec![Self::N, Self::S, Self::E]),
-            Self::S => (Self::N, vec![Self::W, Self::E, Self::S]),
-            Self::W => (Self::E, vec![Self::N, Self::S, Self::W]),
-        }
+use adventofcode2019::grid::{Direction, Grid, Point, SparseGrid};
+use adventofcode2019::replay::{Replay, Session};
+use adventofcode2019::search::{self, Search};
+use adventofcode2019::{program_from_stdin, State, VM};
+use std::collections::VecDeque;
+use std::env;
+use std::io;
+
+/* How the droid is told to move. */
+fn command(d: Direction) -> i64 {
+    match d {
+        Direction::N => 1,
+        Direction::S => 2,
+        Direction::W => 3,
+        Direction::E => 4,
     }
 }
 
@@ -51,137 +25,200 @@ const WALL: char = '█';
 const VALVE: char = '⊛';
 const CORRIDOR: char = '·';
 
-struct Game {
-    known: BTreeMap<Point, char>,
-    cost: BTreeMap<Point, usize>,
-    droid: Point,
-    valve: Option<Point>,
-    stack: Vec<Direction>,
-    last: Option<Direction>,
+const ORIGIN: Point = Point { x: 0, y: 0 };
+
+/* Everything the droid can find out: what's in each cell it can reach, and
+ * in the walls around them.
+ */
+pub struct Maze {
+    known: SparseGrid<char>,
+    oxygen: Point,
 }
 
-impl Game {
-    fn new() -> Self {
-        let mut cost = BTreeMap::new();
-        let mut known = BTreeMap::new();
-        let droid = Point { x: 0, y: 0 };
-        known.insert(*&droid, '.');
-        cost.insert(*&droid, 0);
+impl Maze {
+    /* Explores breadth first, keeping a copy of the droid at each cell on
+     * the frontier, so each cell is found by trying one move from a
+     * neighbour rather than walking back to it.
+     */
+    fn explore(vm: &VM) -> Self {
+        let mut known = SparseGrid::new();
+        known.insert(ORIGIN, CORRIDOR);
+        let mut oxygen = None;
+        let mut frontier = VecDeque::new();
+        frontier.push_back((ORIGIN, vm.clone()));
+        while let Some((p, droid)) = frontier.pop_front() {
+            for d in &Direction::ALL {
+                let target = d.advance(p);
+                if known.contains_key(&target) {
+                    continue;
+                }
+                let mut droid = droid.clone();
+                droid.input.push_back(command(*d));
+                assert_eq!(droid.run(), State::NeedInput);
+                let cell = match droid.output.pop_front() {
+                    Some(0) => WALL,
+                    Some(1) => CORRIDOR,
+                    Some(2) => VALVE,
+                    status => panic!("droid says {:?}", status),
+                };
+                known.insert(target, cell);
+                if cell == VALVE {
+                    oxygen = Some(target);
+                }
+                if cell != WALL {
+                    frontier.push_back((target, droid));
+                }
+            }
+        }
         Self {
             known,
-            cost,
-            droid,
-            valve: None,
-            stack: vec![Direction::N, Direction::E, Direction::S, Direction::W],
-            last: None,
+            oxygen: oxygen.expect("no oxygen system"),
         }
     }
 
-    fn render_cell(&self, p: Point) -> char {
-        if p == self.droid {
-            '⚙'
-        } else {
-            *self.known.get(&p).unwrap_or(&' ')
-        }
+    fn open(&self, p: Point) -> bool {
+        self.known.get(&p).is_some_and(|&c| c != WALL)
+    }
+
+    /* The open cells next to p. */
+    fn moves(&self, p: &Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbours().filter(move |&q| self.open(q))
+    }
+
+    /* The fewest moves to each open cell from start. */
+    fn search(&self, start: Point) -> Search<Point> {
+        search::bfs(start, |p| self.moves(p))
+    }
+
+    /* A shortest sequence of moves from start to end. */
+    fn path(&self, start: Point, end: Point) -> Vec<Direction> {
+        let cells = self.search(start).path(&end).expect("unreachable");
+        cells
+            .windows(2)
+            .map(|step| {
+                let d = Direction::ALL
+                    .iter()
+                    .find(|d| d.advance(step[0]) == step[1]);
+                *d.unwrap()
+            })
+            .collect()
     }
 
     fn render(&self) {
-        let pmin = Point {
-            x: self.known.keys().map(|p| p.x).min().unwrap(),
-            y: self.known.keys().map(|p| p.y).min().unwrap(),
-        };
-        let pmax = Point {
-            x: self.known.keys().map(|p| p.x).max().unwrap(),
-            y: self.known.keys().map(|p| p.y).max().unwrap(),
-        };
-        for y in (pmin.y..pmax.y + 1).rev() {
-            let line = (pmin.x..pmax.x + 1)
-                .map(|x| self.render_cell(Point { x, y }))
-                .collect::<String>();
-            println!("{}", line);
-        }
-        println!("");
+        let maze = self.known.render(|p, cell| match cell {
+            _ if p == ORIGIN => '⚙',
+            Some(&c) => c,
+            None => ' ',
+        });
+        println!("{}", maze);
     }
+}
 
-    fn update(&mut self, vm: &mut VM) {
-        if let Some(d) = &self.last {
-            let mut o = vm.drain_output();
-            let result = o.pop_front().unwrap();
-            assert_eq!(o.len(), 0);
-            let target = d.advance(self.droid);
-            let cost = self.cost[&self.droid] + 1;
-            if result == 0 {
-                self.known.insert(target, WALL);
-            } else {
-                if result == 2 {
-                    self.valve = Some(target);
-                }
+/* Walks the droid along a path, checking it gets through. */
+fn walk(vm: &mut VM, path: &[Direction]) -> i64 {
+    let mut status = 1;
+    for d in path {
+        vm.input.push_back(command(*d));
+        assert_eq!(vm.run(), State::NeedInput);
+        status = vm.output.pop_front().expect("status");
+        assert_ne!(status, 0, "walked into a wall");
+    }
+    status
+}
 
-                self.droid = target;
-                if !self.known.contains_key(&target) {
-                    self.known
-                        .insert(target, if result == 2 { VALVE } else { CORRIDOR });
-                    self.cost.insert(target, cost);
-                    let (back, succs) = d.succs();
-                    self.stack.push(back);
-                    for succ in succs {
-                        if !self.known.contains_key(&succ.advance(self.droid)) {
-                            self.stack.push(succ);
-                        }
-                    }
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = args.iter().map(String::as_str);
+    let (mut animate, mut csv) = (false, false);
+    let mut fps = 30;
+    let (mut save, mut load) = (None, None);
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--animate" => animate = true,
+            "--csv" => csv = true,
+            "--save" => save = Some(args.next().expect("--save PATH")),
+            "--load" => load = Some(args.next().expect("--load PATH")),
+            "--fps" => fps = args.next().and_then(|n| n.parse().ok()).expect("--fps N"),
+            _ => rest.push(arg),
+        }
+    }
+    let session = Session::parse(&rest);
+
+    let mut vm = None;
+    let maze = match load {
+        /* A saved maze needs no droid to find it. */
+        Some(path) => {
+            assert!(rest.is_empty(), "a loaded maze has no droid to record");
+            Maze::load(path).expect(path)
+        }
+        None => {
+            let mut droid = VM::new(&program_from_stdin());
+            if let Session::Replay(recording) = session {
+                let mut replay = Replay::new(recording);
+                match replay.verify(&mut droid) {
+                    Ok(_) => println!("replayed {} steps", droid.steps()),
+                    Err(d) => panic!("{}", d),
                 }
-            };
+                return;
+            }
+            let maze = Maze::explore(&droid);
+            vm = Some(droid);
+            maze
         }
+    };
+    if let Some(path) = save {
+        maze.save(path).expect(path);
     }
 
-    fn submit(&mut self, vm: &mut VM) -> bool {
-        self.last = self.stack.pop();
-        if let Some(d) = &self.last {
-            vm.input.push_back(*d as i64);
-            true
-        } else {
-            false
-        }
+    if csv {
+        oxygen::csv(&maze, io::stdout().lock()).expect("stdout");
+    } else if animate {
+        let minutes = oxygen::animate(&maze, fps);
+        println!("{}", minutes);
+    } else {
+        maze.render();
+        /* Part 1: how far it is to the oxygen system. */
+        let distance = maze.search(ORIGIN).distance(&maze.oxygen).unwrap();
+        println!("{} {}", maze.oxygen, distance);
+        /* Part 2: how long the oxygen takes to reach the furthest cell. */
+        println!("{}", search::fill_time(maze.oxygen, |p| maze.moves(p)));
     }
 
-    fn reset(&mut self) {
-        let p = self.valve.unwrap();
-        assert_eq!(p, self.droid);
-        self.known.clear();
-        self.known.insert(p, VALVE);
-        self.cost.clear();
-        self.cost.insert(p, 0);
-        self.stack = vec![Direction::N, Direction::E, Direction::S, Direction::W];
+    /* The explorers were copies; what's recorded is the droid itself
+     * taking the shortest way to the oxygen system.
+     */
+    if let Some(vm) = &mut vm {
+        session.start(vm);
+        walk(vm, &maze.path(ORIGIN, maze.oxygen));
+        session.finish(vm);
     }
 }
 
-fn main() {
-    let program = program_from_stdin();
-    let mut vm = VM::new(&program);
-    let mut game = Game::new();
-    game.submit(&mut vm);
-    while let State::NeedInput = vm.run() {
-        game.update(&mut vm);
-        if let Some(p) = &game.valve {
-            println!("{} {}", p, game.cost[p]);
-            game.render();
-            break;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::data_program;
 
-        if !game.submit(&mut vm) {
-            panic!("oh no");
-        }
+    fn droid() -> VM {
+        VM::new(&data_program("day15-input"))
     }
 
-    /* Now do it again, starting at the same point. */
-    game.reset();
-    while game.submit(&mut vm) {
-        if let State::NeedInput = vm.run() {
-            game.update(&mut vm);
-        } else {
-            break;
+    #[test]
+    fn test_maze() {
+        let vm = droid();
+        let maze = Maze::explore(&vm);
+        /* Nothing next to an open cell is left unknown. */
+        for (&p, _) in maze.known.iter().filter(|&(_, &c)| c != WALL) {
+            for d in &Direction::ALL {
+                assert!(maze.known.contains_key(&d.advance(p)), "{}", p);
+            }
         }
+        assert_eq!(maze.search(ORIGIN).distance(&maze.oxygen), Some(234));
+        assert_eq!(search::fill_time(maze.oxygen, |p| maze.moves(p)), 292);
+
+        let path = maze.path(ORIGIN, maze.oxygen);
+        assert_eq!(path.len(), 234);
+        assert_eq!(walk(&mut vm.clone(), &path), 2);
     }
-    game.render();
-    println!("{}", game.cost.values().max().unwrap());
 }
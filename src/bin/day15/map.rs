@@ -0,0 +1,177 @@
+use crate::{Maze, CORRIDOR, ORIGIN, VALVE, WALL};
+use adventofcode2019::grid::{Grid, Point, SparseGrid};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* A maze is saved as a map, like
+ *
+ *   origin 1 2
+ *   legend █ wall · corridor ⊛ oxygen
+ *    █
+ *   █⊛█
+ *   █·
+ *    █
+ *
+ * where the origin is the column and row the droid started at, and the
+ * legend says which character is which.  Unknown cells are spaces.
+ */
+impl fmt::Display for Maze {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds = self.known.bounds().expect("the origin is always known");
+        let origin = ORIGIN - bounds.min;
+        writeln!(f, "origin {} {}", origin.x, origin.y)?;
+        writeln!(
+            f,
+            "legend {} wall {} corridor {} oxygen",
+            WALL, CORRIDOR, VALVE
+        )?;
+        let map = self.known.render(|_, cell| *cell.unwrap_or(&' '));
+        for line in map.lines() {
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/* The words on a line after its keyword. */
+fn header<'a>(line: Option<&'a str>, keyword: &str) -> io::Result<Vec<&'a str>> {
+    let mut words = line.unwrap_or("").split_whitespace();
+    if words.next() == Some(keyword) {
+        Ok(words.collect())
+    } else {
+        Err(invalid(format!("expected {}", keyword)))
+    }
+}
+
+impl std::str::FromStr for Maze {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut lines = s.lines();
+        let origin = header(lines.next(), "origin")?
+            .iter()
+            .map(|n| {
+                n.parse()
+                    .map_err(|_| invalid(format!("bad origin {:?}", n)))
+            })
+            .collect::<io::Result<Vec<i64>>>()?;
+        let origin = match origin[..] {
+            [x, y] => Point::new(x, y),
+            _ => return Err(invalid("expected origin X Y".to_string())),
+        };
+
+        let legend = header(lines.next(), "legend")?;
+        let mut glyphs = Vec::new();
+        for pair in legend.chunks(2) {
+            let (glyph, cell) = match pair {
+                [glyph, "wall"] => (glyph, WALL),
+                [glyph, "corridor"] => (glyph, CORRIDOR),
+                [glyph, "oxygen"] => (glyph, VALVE),
+                _ => return Err(invalid(format!("bad legend {:?}", pair))),
+            };
+            let mut chars = glyph.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => glyphs.push((c, cell)),
+                _ => return Err(invalid(format!("bad glyph {:?}", glyph))),
+            }
+        }
+
+        let mut known = SparseGrid::new();
+        let mut oxygen = None;
+        for (y, line) in lines.enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let p = Point::new(x as i64, y as i64) - origin;
+                let cell = match glyphs.iter().find(|&&(g, _)| g == c) {
+                    Some(&(_, cell)) => cell,
+                    None => return Err(invalid(format!("{:?} at {} isn't in the legend", c, p))),
+                };
+                if cell == VALVE && oxygen.replace(p).is_some() {
+                    return Err(invalid(format!("a second oxygen system at {}", p)));
+                }
+                known.insert(p, cell);
+            }
+        }
+        if known.get(&ORIGIN).is_none_or(|&c| c == WALL) {
+            return Err(invalid("the origin isn't open".to_string()));
+        }
+        Ok(Self {
+            known,
+            oxygen: oxygen.ok_or_else(|| invalid("no oxygen system".to_string()))?,
+        })
+    }
+}
+
+impl Maze {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::{data_program, search, VM};
+
+    #[test]
+    fn test_save() {
+        let maze = Maze::explore(&VM::new(&data_program("day15-input")));
+        let text = maze.to_string();
+        assert!(text.starts_with("origin 21 21\nlegend █ wall · corridor ⊛ oxygen\n"));
+        let loaded: Maze = text.parse().unwrap();
+        assert_eq!(loaded.known, maze.known);
+        assert_eq!(loaded.oxygen, maze.oxygen);
+        assert_eq!(loaded.to_string(), text);
+
+        let path = std::env::temp_dir().join(format!("day15-{}.map", std::process::id()));
+        maze.save(&path).unwrap();
+        let loaded = Maze::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded.search(ORIGIN).distance(&loaded.oxygen), Some(234));
+    }
+
+    #[test]
+    fn test_load() {
+        let map = "origin 1 2\nlegend # wall . corridor O oxygen\n ###\n#O.#\n#..#\n ##";
+        let maze: Maze = map.parse().unwrap();
+        assert_eq!(maze.oxygen, Point::new(0, -1));
+        assert_eq!(maze.known.get(&Point::new(-1, 0)), Some(&WALL));
+        assert_eq!(maze.known.get(&Point::new(1, 0)), Some(&CORRIDOR));
+        assert_eq!(maze.known.get(&Point::new(-1, -2)), None);
+        assert_eq!(search::fill_time(maze.oxygen, |p| maze.moves(p)), 2);
+
+        let legend = "legend # wall . corridor O oxygen\n";
+        for (bad, error) in &[
+            ("", "expected origin"),
+            ("origin 1\n", "expected origin X Y"),
+            ("origin 0 0\nlegend # floor\n", "bad legend"),
+            ("origin 0 0\nlegend ## wall\n", "bad glyph"),
+            ("origin 0 0\n", "expected legend"),
+        ] {
+            let e = bad.parse::<Maze>().err().unwrap();
+            assert!(e.to_string().starts_with(error), "{}", e);
+        }
+        for (bad, error) in &[
+            ("origin 0 0\n.x", "'x' at (1, 0) isn't in the legend"),
+            ("origin 0 0\n.OO", "a second oxygen system at (2, 0)"),
+            ("origin 0 0\n..", "no oxygen system"),
+            ("origin 1 0\n.#O", "the origin isn't open"),
+        ] {
+            let text = bad.replacen('\n', &format!("\n{}", legend), 1);
+            let e = text.parse::<Maze>().err().unwrap();
+            assert_eq!(e.to_string(), *error);
+        }
+    }
+}
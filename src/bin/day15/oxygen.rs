@@ -0,0 +1,145 @@
+use crate::{Maze, CORRIDOR, WALL};
+use adventofcode2019::grid::{Grid, Point};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+pub const OXYGEN: char = '░';
+
+/* Oxygen spreading out from the oxygen system, a cell further each minute. */
+pub struct Oxygen<'a> {
+    maze: &'a Maze,
+    pub filled: BTreeSet<Point>,
+    /* The cells filled in the last minute. */
+    pub frontier: Vec<Point>,
+    pub minutes: usize,
+}
+
+impl<'a> Oxygen<'a> {
+    pub fn new(maze: &'a Maze) -> Self {
+        let mut filled = BTreeSet::new();
+        filled.insert(maze.oxygen);
+        Self {
+            maze,
+            filled,
+            frontier: vec![maze.oxygen],
+            minutes: 0,
+        }
+    }
+
+    /* Spreads for a minute.  Returns false, and leaves everything as it
+     * was, once there's nowhere left to go.
+     */
+    pub fn step(&mut self) -> bool {
+        let mut frontier = Vec::new();
+        for p in &self.frontier {
+            for q in self.maze.moves(p) {
+                if self.filled.insert(q) {
+                    frontier.push(q);
+                }
+            }
+        }
+        if frontier.is_empty() {
+            return false;
+        }
+        self.frontier = frontier;
+        self.minutes += 1;
+        true
+    }
+
+    pub fn render(&self) -> String {
+        self.maze.known.render(|p, cell| match cell {
+            _ if self.filled.contains(&p) => OXYGEN,
+            Some(&WALL) => WALL,
+            Some(_) => CORRIDOR,
+            None => ' ',
+        })
+    }
+}
+
+/* Runs the simulation to the end, calling f after each minute, including
+ * the minute nothing has spread yet.  Returns how many minutes it took.
+ */
+pub fn simulate<F: FnMut(&Oxygen)>(maze: &Maze, mut f: F) -> usize {
+    let mut oxygen = Oxygen::new(maze);
+    f(&oxygen);
+    while oxygen.step() {
+        f(&oxygen);
+    }
+    oxygen.minutes
+}
+
+/* Redraws the maze in place for each minute, fps times a second. */
+pub fn animate(maze: &Maze, fps: u32) -> usize {
+    let open = maze.known.values().filter(|&&c| c != WALL).count();
+    let mut stdout = io::stdout();
+    /* Clear the screen and hide the cursor. */
+    print!("\x1b[2J\x1b[?25l");
+    let minutes = simulate(maze, |oxygen| {
+        let status = format!(
+            "\x1b[7m minute {:>4} | frontier {:>3} | filled {:>4} of {} \x1b[0m\x1b[K",
+            oxygen.minutes,
+            oxygen.frontier.len(),
+            oxygen.filled.len(),
+            open
+        );
+        println!("\x1b[H{}{}", oxygen.render(), status);
+        stdout.flush().unwrap();
+        thread::sleep(Duration::from_secs(1) / fps.max(1));
+    });
+    print!("\x1b[?25h");
+    stdout.flush().unwrap();
+    minutes
+}
+
+/* The size of the frontier each minute, for plotting. */
+pub fn csv<W: Write>(maze: &Maze, mut out: W) -> io::Result<usize> {
+    writeln!(out, "minute,frontier,filled")?;
+    let mut result = Ok(());
+    let minutes = simulate(maze, |oxygen| {
+        if result.is_ok() {
+            result = writeln!(
+                out,
+                "{},{},{}",
+                oxygen.minutes,
+                oxygen.frontier.len(),
+                oxygen.filled.len()
+            );
+        }
+    });
+    result.map(|_| minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::{data_program, VM};
+
+    #[test]
+    fn test_oxygen() {
+        let maze = Maze::explore(&VM::new(&data_program("day15-input")));
+        let layers = maze.search(maze.oxygen);
+        let mut frontiers = Vec::new();
+        let minutes = simulate(&maze, |o| frontiers.push(o.frontier.len()));
+        assert_eq!(minutes, 292);
+        /* Each minute reaches the cells that far from the oxygen system. */
+        for (minute, &n) in frontiers.iter().enumerate() {
+            let cells = layers.distances.values().filter(|&&d| d == minute);
+            assert_eq!(cells.count(), n, "minute {}", minute);
+        }
+
+        let mut out = Vec::new();
+        assert_eq!(csv(&maze, &mut out).unwrap(), 292);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 1 + 293);
+        assert_eq!(lines[..2], ["minute,frontier,filled", "0,1,1"]);
+        let open = maze.known.values().filter(|&&c| c != WALL).count();
+        assert!(lines[293].ends_with(&format!(",{}", open)));
+
+        let mut o = Oxygen::new(&maze);
+        o.step();
+        assert_eq!(o.render().matches(OXYGEN).count(), 1 + frontiers[1]);
+    }
+}
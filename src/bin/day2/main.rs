@@ -1,8 +1,7 @@
 use std::io::{self, BufRead};
-use std::cmp;
 
-fn evaluate(program: &Vec<usize>, noun: usize, verb: usize) -> usize {
-    let mut memory = program.clone();
+fn evaluate(program: &[usize], noun: usize, verb: usize) -> usize {
+    let mut memory = program.to_vec();
     memory[1] = noun;
     memory[2] = verb;
 
@@ -19,10 +18,10 @@ fn evaluate(program: &Vec<usize>, noun: usize, verb: usize) -> usize {
         let k = memory[ip + 2];
         let l = memory[ip + 3];
         match memory[ip] {
-            1  => memory[l] = memory[j] + memory[k],
-            2  => memory[l] = memory[j] * memory[k],
+            1 => memory[l] = memory[j] + memory[k],
+            2 => memory[l] = memory[j] * memory[k],
             99 => break,
-            _  => panic!("at the discotheque {}", ip)
+            _ => panic!("at the discotheque {}", ip),
         }
         ip += 4
     }
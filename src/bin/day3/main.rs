@@ -1,23 +1,12 @@
+use adventofcode2019::grid::{self, Direction};
 use std::cmp::{max, min};
 use std::fmt;
 use std::io::{self, BufRead};
 
-#[derive(Debug, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
-}
+type Point = grid::Point<i32>;
 
-impl fmt::Display for Point {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
-    }
-}
-
-#[derive(Debug, PartialEq, Eq)]
-enum Direction {
-    Horizontal,
-    Vertical,
+fn horizontal(dir: Direction) -> bool {
+    dir == Direction::E || dir == Direction::W
 }
 
 #[derive(Debug)]
@@ -32,18 +21,8 @@ struct Segment {
 
 impl fmt::Display for Segment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let points = match self.dir {
-            Direction::Horizontal => (
-                Point {
-                    x: self.p,
-                    y: self.start,
-                },
-                Point {
-                    x: self.p,
-                    y: self.end,
-                },
-            ),
-            Direction::Vertical => (
+        let points = if horizontal(self.dir) {
+            (
                 Point {
                     x: self.start,
                     y: self.p,
@@ -52,7 +31,18 @@ impl fmt::Display for Segment {
                     x: self.end,
                     y: self.p,
                 },
-            ),
+            )
+        } else {
+            (
+                Point {
+                    x: self.p,
+                    y: self.start,
+                },
+                Point {
+                    x: self.p,
+                    y: self.end,
+                },
+            )
         };
         write!(f, "{} → {}", points.0, points.1)
     }
@@ -63,15 +53,16 @@ type Crossing = (Point, i32);
 
 fn intersects(a: &Segment, b: &Segment) -> Option<Crossing> {
     // yuck
-    if a.dir != b.dir
+    if horizontal(a.dir) != horizontal(b.dir)
         && min(a.start, a.end) <= b.p
         && b.p <= max(a.start, a.end)
         && min(b.start, b.end) <= a.p
         && a.p <= max(b.start, b.end)
     {
-        let point = match a.dir {
-            Direction::Horizontal => Point { x: a.p, y: b.p },
-            Direction::Vertical => Point { x: b.p, y: a.p },
+        let point = if horizontal(a.dir) {
+            Point { x: b.p, y: a.p }
+        } else {
+            Point { x: a.p, y: b.p }
         };
         let a_partial = (b.p - a.start).abs();
         let b_partial = (a.p - b.start).abs();
@@ -83,56 +74,38 @@ fn intersects(a: &Segment, b: &Segment) -> Option<Crossing> {
 }
 
 fn parse_line(line: String) -> Wire {
-    let mut x = 0;
-    let mut y = 0;
+    let mut at = Point::default();
     let mut dist = 0;
     let mut wire = Vec::new();
 
     for op in line.split(",") {
         let len: i32 = op[1..].parse().unwrap();
-        match &op[0..1] {
-            "L" => {
-                wire.push(Segment {
-                    dir: Direction::Horizontal,
-                    dist,
-                    p: y,
-                    start: x,
-                    end: x - len,
-                });
-                x -= len;
-            }
-            "R" => {
-                wire.push(Segment {
-                    dir: Direction::Horizontal,
-                    dist,
-                    p: y,
-                    start: x,
-                    end: x + len,
-                });
-                x += len;
-            }
-            "U" => {
-                wire.push(Segment {
-                    dir: Direction::Vertical,
-                    dist,
-                    p: x,
-                    start: y,
-                    end: y + len,
-                });
-                y += len;
-            }
-            "D" => {
-                wire.push(Segment {
-                    dir: Direction::Vertical,
-                    dist,
-                    p: x,
-                    start: y,
-                    end: y - len,
-                });
-                y -= len;
-            }
+        let dir = match &op[0..1] {
+            "L" => Direction::W,
+            "R" => Direction::E,
+            "U" => Direction::N,
+            "D" => Direction::S,
             _ => panic!("{}", op),
-        }
+        };
+        /* The puzzle's y goes up, and the grid's down. */
+        let delta: Point = dir.delta();
+        let next = Point {
+            x: at.x + delta.x * len,
+            y: at.y - delta.y * len,
+        };
+        let (p, start, end) = if horizontal(dir) {
+            (at.y, at.x, next.x)
+        } else {
+            (at.x, at.y, next.y)
+        };
+        wire.push(Segment {
+            dir,
+            dist,
+            p,
+            start,
+            end,
+        });
+        at = next;
         dist += len;
     }
 
@@ -140,8 +113,8 @@ fn parse_line(line: String) -> Wire {
 }
 
 fn crossings(w0: &Wire, w1: &Wire) -> Vec<Crossing> {
-    w0.into_iter()
-        .flat_map(move |a| w1.into_iter().filter_map(move |b| intersects(&a, &b)))
+    w0.iter()
+        .flat_map(move |a| w1.iter().filter_map(move |b| intersects(a, b)))
         .collect()
 }
 
@@ -154,7 +127,7 @@ fn main() {
     let w1 = &wires[1];
     println!("{:?}", w1);
 
-    let points = crossings(&w0, &w1);
+    let points = crossings(w0, w1);
 
     for point in &points {
         println!("{:?}", point);
@@ -162,7 +135,7 @@ fn main() {
     let part1 = points
         .clone()
         .into_iter()
-        .map(|(p, _)| p.x.abs() + p.y.abs())
+        .map(|(p, _)| p.manhattan())
         .filter(|d| d > &0)
         .min();
     println!("{:?}", part1);
@@ -175,3 +148,25 @@ fn main() {
     println!("{:?}", part2);
     /* Just take the min non-zero manhattan */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossings() {
+        let w0 = parse_line(String::from("R8,U5,L5,D3"));
+        let w1 = parse_line(String::from("U7,R6,D4,L4"));
+        assert_eq!(w0[1].to_string(), "(8, 0) → (8, 5)");
+        let mut points = crossings(&w0, &w1);
+        points.sort();
+        assert_eq!(
+            points,
+            vec![
+                (Point::new(0, 0), 0),
+                (Point::new(3, 3), 40),
+                (Point::new(6, 5), 30),
+            ]
+        );
+    }
+}
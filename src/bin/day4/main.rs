@@ -9,7 +9,7 @@ fn satisfies_part1(password: u32) -> bool {
         pairs_equal |= i == j;
         monotonic &= i <= j;
     }
-    return pairs_equal && monotonic;
+    pairs_equal && monotonic
 }
 
 fn satisfies_part2(password: u32) -> bool {
@@ -23,11 +23,12 @@ fn satisfies_part2(password: u32) -> bool {
 
         // u g l y
         // itertools' zip_longest is only for 2-tuples, unlike Python's.
-        if (i == 1 || slice[i - 2] != slice[i - 1]) &&
-           slice[i - 1] == slice[i] &&
-           (i == ps.len() - 1 || slice[i] != slice[i + 1]) {
-               pairs_equal = true;
-           }
+        if (i == 1 || slice[i - 2] != slice[i - 1])
+            && slice[i - 1] == slice[i]
+            && (i == ps.len() - 1 || slice[i] != slice[i + 1])
+        {
+            pairs_equal = true;
+        }
     }
     pairs_equal
 }
@@ -9,9 +9,9 @@ struct VM {
 }
 
 impl VM {
-    fn new(program: &Vec<i64>, input: Vec<i64>) -> Self {
+    fn new(program: &[i64], input: Vec<i64>) -> Self {
         VM {
-            memory: program.clone(),
+            memory: program.to_vec(),
             ip: 0,
             input,
             output: Vec::new(),
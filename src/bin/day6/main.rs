@@ -1,3 +1,4 @@
+use adventofcode2019::search;
 use std::collections::BTreeMap;
 use std::io::{self, BufRead};
 /*
@@ -20,28 +21,6 @@ impl Node {
 
 type Children<'a> = BTreeMap<&'a str, Vec<&'a str>>;
 
-fn f(n: &str, d: u32, children: &Children) -> u32 {
-    let e: u32 = match children.get(n) {
-        None => 0,
-        Some(cs) => cs.iter().map(|c| f(c, d + 1, children)).sum(),
-    };
-    d + e
-}
-
-fn ancestors<'a>(mut n: &'a str, parents: &BTreeMap<&'a str, &'a str>) -> Vec<&'a str> {
-    let mut v = Vec::new();
-    loop {
-        match parents.get(n) {
-            None => break,
-            Some(p) => {
-                v.push(*p);
-                n = p;
-            }
-        }
-    }
-    v
-}
-
 fn main() {
     let stdin = io::stdin();
     let handle = stdin.lock();
@@ -52,18 +31,23 @@ fn main() {
     let lines: Vec<String> = handle.lines().map(|line| line.unwrap()).collect();
     let rows: Vec<Vec<&str>> = lines.iter().map(|line| line.split(')').collect()).collect();
     for row in rows {
-        children.entry(row[0]).or_insert(Vec::new()).push(row[1]);
+        children.entry(row[0]).or_default().push(row[1]);
         parents.insert(row[1], row[0]);
     }
 
-    let part1 = f("COM", 0, &children);
+    /* Each orbit, direct or not, is a step on the way down from COM. */
+    let orbits = search::bfs("COM", |n| children.get(n).cloned().unwrap_or_default());
+    let part1: usize = orbits.distances.values().sum();
     println!("{}", part1);
 
-    let mut you = ancestors("YOU", &parents);
-    let mut san = ancestors("SAN", &parents);
-    while you.last() == san.last() {
-        you.pop();
-        san.pop();
+    /* Transfers go up or down the tree, from what YOU orbit to what SAN do. */
+    let neighbours = |n: &&str| {
+        let mut ns = children.get(n).cloned().unwrap_or_default();
+        ns.extend(parents.get(n));
+        ns
+    };
+    if let (Some(you), Some(san)) = (parents.get("YOU"), parents.get("SAN")) {
+        let transfers = search::bfs(*you, neighbours).distance(san);
+        println!("{}", transfers.expect("no way to SAN"));
     }
-    println!("{}", you.len() + san.len());
 }
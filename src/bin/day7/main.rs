@@ -1,104 +1,268 @@
+extern crate adventofcode2019;
+
+use adventofcode2019::network::{Network, NodeId, Stop};
+use adventofcode2019::{program_from_stdin, Machine, Memory, VM};
 use itertools::Itertools;
-use std::collections::VecDeque;
-use std::io::{self, BufRead};
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+mod compiled {
+    include!(concat!(env!("OUT_DIR"), "/day7.rs"));
+}
 
-type Memory = Vec<i64>;
-type IO = VecDeque<i64>;
+/* Highest signal, and the phase sequence which produces it. */
+type Best = (i64, Vec<i64>);
 
-struct VM {
-    memory: Memory,
-    ip: usize,
-    input: IO,
-    output: IO,
+fn better(a: &Best, b: &Best) -> bool {
+    /* Break ties towards the lexicographically smallest sequence, so the
+     * answer doesn't depend on which thread finishes first.
+     */
+    a.0 > b.0 || (a.0 == b.0 && a.1 < b.1)
 }
 
-enum State {
-    Running,
-    Halted,
-    NeedInput,
+/* Our puzzle input is transpiled ahead of time by build.rs; anything else
+ * is interpreted.
+ */
+fn amplifier(program: &Memory) -> Box<dyn Machine> {
+    if program.as_slice() == compiled::Day7::PROGRAM {
+        Box::new(compiled::Day7::new())
+    } else {
+        Box::new(VM::new(program))
+    }
 }
 
-impl VM {
-    fn new(program: &Memory, input: IO) -> Self {
-        VM {
-            memory: program.clone(),
-            ip: 0,
-            input,
-            output: IO::new(),
-        }
+/* One amplifier per phase setting, each fed by the one before. */
+fn amplifiers(program: &Memory, sequence: &[i64]) -> (Network, Vec<NodeId>) {
+    let mut network = Network::new();
+    let nodes: Vec<NodeId> = sequence
+        .iter()
+        .enumerate()
+        .map(|(i, &phase)| {
+            let name = ((b'A' + i as u8) as char).to_string();
+            let node = network.add_machine(&name, amplifier(program));
+            network.input(node, phase);
+            node
+        })
+        .collect();
+    for pair in nodes.windows(2) {
+        network.connect(pair[0], pair[1]);
     }
+    network.input(nodes[0], 0);
+    (network, nodes)
+}
+
+/* Searches the chained variant depth-first.  The signal out of an amplifier
+ * depends only on its phase and input signal, and the best way to finish the
+ * chain depends only on the phases left and the signal so far, so both are
+ * memoized: identical prefixes are only ever run once.  Branches which would
+ * use a phase equal to one already tried at the same point are pruned, as
+ * they can only find the same chains again.
+ */
+struct Chain<'a> {
+    program: &'a Memory,
+    phases: &'a [i64],
+    amplifiers: usize,
+    outputs: HashMap<(i64, i64), i64>,
+    best: HashMap<(u64, i64), Best>,
+}
 
-    fn arg(&self, i: u32) -> i64 {
-        let instruction = self.memory[self.ip];
-        let mode = (instruction / (10 * (10_i64.pow(i)))) % 10;
-        let val = self.memory[self.ip + i as usize];
-        match mode {
-            0 => self.memory[val as usize],
-            1 => val,
-            _ => panic!("{}", mode),
+impl<'a> Chain<'a> {
+    fn new(program: &'a Memory, phases: &'a [i64], amplifiers: usize) -> Self {
+        Self {
+            program,
+            phases,
+            amplifiers,
+            outputs: HashMap::new(),
+            best: HashMap::new(),
         }
     }
 
-    fn int3<F>(&mut self, f: F)
-    where
-        F: Fn(i64, i64) -> i64,
-    {
-        let l = self.memory[self.ip + 3];
-        self.memory[l as usize] = f(self.arg(1), self.arg(2));
-        self.ip += 4
+    fn amplify(&mut self, phase: i64, signal: i64) -> i64 {
+        let program = self.program;
+        *self.outputs.entry((phase, signal)).or_insert_with(|| {
+            let mut machine = amplifier(program);
+            machine.vm_mut().input.extend(&[phase, signal]);
+            machine.run();
+            machine
+                .vm_mut()
+                .output
+                .pop_front()
+                .expect("amplifier output")
+        })
     }
 
-    fn step(&mut self) -> State {
-        let instruction = self.memory[self.ip];
-        match instruction % 100 {
-            1 => self.int3(|a, b| a + b),
-            2 => self.int3(|a, b| a * b),
-            3 => {
-                let j = self.memory[self.ip + 1];
-                match self.input.pop_front() {
-                    Some(val) => {
-                        self.memory[j as usize] = val;
-                        self.ip += 2;
-                    }
-                    None => {
-                        return State::NeedInput;
-                    }
-                }
-            }
-            4 => {
-                self.output.push_back(self.arg(1));
-                self.ip += 2
-            }
-            5 => {
-                if self.arg(1) != 0 {
-                    self.ip = self.arg(2) as usize
-                } else {
-                    self.ip += 3;
-                }
-            }
-            6 => {
-                if self.arg(1) == 0 {
-                    self.ip = self.arg(2) as usize
-                } else {
-                    self.ip += 3;
-                }
+    fn search(&mut self, used: u64, signal: i64) -> Best {
+        if used.count_ones() as usize == self.amplifiers {
+            return (signal, Vec::new());
+        }
+        if let Some(best) = self.best.get(&(used, signal)) {
+            return best.clone();
+        }
+        let mut best: Option<Best> = None;
+        for i in firsts(self.phases, used) {
+            let candidate = self.start(used, i, signal);
+            if best.as_ref().is_none_or(|b| better(&candidate, b)) {
+                best = Some(candidate);
             }
-            7 => self.int3(|a, b| (a < b) as i64),
-            8 => self.int3(|a, b| (a == b) as i64),
-            99 => return State::Halted,
-            x => panic!("the discotheque: ip {}: {}", self.ip, x),
         }
-        State::Running
+        let best = best.expect("enough phases for every amplifier");
+        self.best.insert((used, signal), best.clone());
+        best
+    }
+
+    /* Best chain from here whose next amplifier uses phases[i]. */
+    fn start(&mut self, used: u64, i: usize, signal: i64) -> Best {
+        let phase = self.phases[i];
+        let output = self.amplify(phase, signal);
+        let (value, mut rest) = self.search(used | (1 << i), output);
+        rest.insert(0, phase);
+        (value, rest)
     }
+}
 
-    fn run(&mut self) -> State {
-        loop {
-            match self.step() {
-                State::Running => {}
-                x => return x,
+fn amplify_loop(program: &Memory, sequence: &[i64]) -> i64 {
+    let (mut network, nodes) = amplifiers(program, sequence);
+    let last = *nodes.last().unwrap();
+    network.connect(last, nodes[0]);
+    let sink = network.add_sink(last);
+    let mut signal = None;
+    loop {
+        match network.run_until(sink) {
+            Ok(value) => signal = Some(value),
+            Err(Stop::Halted) => {
+                return signal.expect("at least one output when final machine halts")
             }
+            Err(stop) => panic!("{}", stop),
+        }
+    }
+}
+
+/* The unused phases, skipping any equal to an earlier unused one. */
+fn firsts(phases: &[i64], used: u64) -> Vec<usize> {
+    let unused = |i: &usize| used & (1 << i) == 0;
+    let mut firsts: Vec<usize> = Vec::new();
+    for i in (0..phases.len()).filter(unused) {
+        if firsts.iter().all(|&j| phases[j] != phases[i]) {
+            firsts.push(i);
+        }
+    }
+    firsts
+}
+
+/* Runs f over every task on all cores, reporting progress on stderr if
+ * given a label.
+ */
+fn parallel_max<T, F>(label: Option<&str>, tasks: &[T], f: F) -> Best
+where
+    T: Sync,
+    F: Fn(&T) -> Best + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(tasks.len());
+    let best = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut best: Option<Best> = None;
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        if i >= tasks.len() {
+                            return best;
+                        }
+                        let candidate = f(&tasks[i]);
+                        let n = done.fetch_add(1, Ordering::SeqCst) + 1;
+                        if let Some(label) = label {
+                            eprint!("\r{}: {}/{}", label, n, tasks.len());
+                        }
+                        if best.as_ref().is_none_or(|b| better(&candidate, b)) {
+                            best = Some(candidate);
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().unwrap()).fold(
+            None,
+            |best: Option<Best>, candidate| match best {
+                Some(b) if !better(&candidate, &b) => Some(b),
+                _ => Some(candidate),
+            },
+        )
+    });
+    if label.is_some() {
+        eprintln!();
+    }
+    best.expect("at least one task")
+}
+
+fn search_chain(program: &Memory, phases: &[i64], amplifiers: usize) -> Best {
+    /* One task per first phase; each gets its own memo tables.  There are
+     * too few, and they're too quick, for progress to be worth showing.
+     */
+    parallel_max(None, &firsts(phases, 0), |&i| {
+        Chain::new(program, phases, amplifiers).start(0, i, 0)
+    })
+}
+
+fn search_loop(program: &Memory, phases: &[i64], amplifiers: usize) -> Best {
+    /* Repeated phases give the same sequence more than once; each is only
+     * run the first time.
+     */
+    let sequences: Vec<Vec<i64>> = phases
+        .iter()
+        .cloned()
+        .permutations(amplifiers)
+        .unique()
+        .collect();
+    parallel_max(Some("loop"), &sequences, |sequence| {
+        (amplify_loop(program, sequence), sequence.clone())
+    })
+}
+
+/* "0-4", "-3--1" or "0,1,2,3,4": a range if some '-' has a number either
+ * side of it, and a list otherwise, so lists can have negative phases.
+ */
+fn parse_phases(s: &str) -> Vec<i64> {
+    let range = s.match_indices('-').find_map(|(i, _)| {
+        let lo = s[..i].parse::<i64>().ok()?;
+        let hi = s[i + 1..].parse::<i64>().ok()?;
+        Some(lo..=hi)
+    });
+    match range {
+        Some(range) => range.collect(),
+        None => s.split(',').map(|p| p.parse().expect(s)).collect(),
+    }
+}
+
+fn main() {
+    let mut phases: Vec<i64> = (0..5).collect();
+    let mut loop_phases: Vec<i64> = (5..10).collect();
+    let mut amplifiers = 5;
+    let args: Vec<String> = env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        match (pair[0].as_str(), pair.get(1)) {
+            ("--phases", Some(arg)) => phases = parse_phases(arg),
+            ("--loop-phases", Some(arg)) => loop_phases = parse_phases(arg),
+            ("--amplifiers", Some(arg)) => amplifiers = arg.parse().expect(arg),
+            _ => panic!("usage: [--phases 0-4] [--loop-phases 5-9] [--amplifiers 5]"),
         }
     }
+    assert!(amplifiers > 0);
+    assert!(amplifiers <= phases.len().min(loop_phases.len()));
+    assert!(phases.len() <= 64);
+
+    let program = program_from_stdin();
+
+    let (part1, sequence) = search_chain(&program, &phases, amplifiers);
+    println!("{} {:?}", part1, sequence);
+
+    let (part2, sequence) = search_loop(&program, &loop_phases, amplifiers);
+    println!("{} {:?}", part2, sequence);
 }
 
 #[cfg(test)]
@@ -106,98 +270,57 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_day2() {
-        let program = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-        let input = VecDeque::new();
-        let mut vm = VM::new(&program, input);
-        vm.run();
-
-        assert_eq!(vm.memory, vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    fn test_amplify() {
+        let program = vec![
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ];
+        let phases: Vec<i64> = (0..5).collect();
+        let mut chain = Chain::new(&program, &phases, 5);
+        assert_eq!(chain.search(0, 0), (54321, vec![0, 1, 2, 3, 4]));
+        assert_eq!(
+            search_chain(&program, &phases, 5),
+            (54321, vec![0, 1, 2, 3, 4])
+        );
     }
 
-    fn run_with_input(program: &Memory, input: IO) -> (Memory, IO) {
-        let mut vm = VM::new(&program, input);
-        vm.run();
+    #[test]
+    fn test_fewer_amplifiers() {
+        /* Outputs 10 * signal + phase. */
+        let program = vec![3, 15, 3, 16, 1002, 16, 10, 16, 1, 15, 16, 16, 4, 16, 99];
+        let phases = vec![3, 1, 4, 2];
+        assert_eq!(search_chain(&program, &phases, 3), (432, vec![4, 3, 2]));
 
-        (vm.memory, vm.output)
+        /* The second 4 is only ever used after the first. */
+        let phases = vec![3, 1, 4, 4, 2];
+        let mut chain = Chain::new(&program, &phases, 3);
+        assert_eq!(chain.search(0, 0), (443, vec![4, 4, 3]));
+        assert!(chain.best.keys().all(|(used, _)| used & 0b1100 != 0b1000));
+        assert_eq!(firsts(&phases, 0), [0, 1, 2, 4]);
+        assert_eq!(firsts(&phases, 0b00100), [0, 1, 3, 4]);
     }
 
     #[test]
-    fn test_day5() {
-        let program = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
-        let input = IO::from(vec![7]);
-        let (_, output) = run_with_input(&program, input);
-        assert_eq!(output, IO::from(vec![1]));
-    }
-}
-
-fn amplify(program: &Memory, sequence: Vec<i64>) -> i64 {
-    // println!("- {:?}", sequence);
-    let mut input = IO::from(vec![0]);
-    for phase in sequence {
-        // NB: prepend
-        input.push_front(phase);
-        // println!("{:?}", input);
-        let mut vm = VM::new(&program, input);
-        vm.run();
-        input = vm.output;
+    fn test_parse_phases() {
+        assert_eq!(parse_phases("0-4"), [0, 1, 2, 3, 4]);
+        assert_eq!(parse_phases("-3--1"), [-3, -2, -1]);
+        assert_eq!(parse_phases("-2-1"), [-2, -1, 0, 1]);
+        assert_eq!(parse_phases("3,1,4"), [3, 1, 4]);
+        assert_eq!(parse_phases("-1,0,2"), [-1, 0, 2]);
+        assert_eq!(parse_phases("-5"), [-5]);
     }
-    // println!("  --> {:?}", input);
-    input.pop_front().expect("at least one output")
-}
 
-fn amplify_loop(program: &Memory, sequence: Vec<i64>) -> i64 {
-    let mut vms: Vec<VM> = sequence
-        .iter()
-        .map(|phase| {
-            let input = IO::from(vec![*phase]);
-            VM::new(&program, input)
-        })
-        .collect();
-    vms[0].input.push_back(0);
-    let n = vms.len();
-    loop {
-        for i in 0..n {
-            let (result, mut output): (State, IO) = {
-                let vm = &mut vms[i];
-                let r = vm.run();
-                let o = vm.output.drain(..).collect();
-                (r, o)
-            };
-            if let State::Halted = result {
-                if i == n - 1 {
-                    return output
-                        .pop_front()
-                        .expect("at least one output when final machine halts");
-                }
-            }
-            let next = &mut vms[(i + 1) % n];
-            next.input.append(&mut output);
-        }
+    #[test]
+    fn test_amplify_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        assert_eq!(amplify_loop(&program, &[9, 8, 7, 6, 5]), 139629729);
+        let phases: Vec<i64> = (5..10).collect();
+        assert_eq!(
+            search_loop(&program, &phases, 5),
+            (139629729, vec![9, 8, 7, 6, 5])
+        );
     }
 }
-
-fn main() {
-    let stdin = io::stdin();
-    let handle = stdin.lock();
-    let line = handle
-        .lines()
-        .map(|l| l.unwrap())
-        .collect::<Vec<String>>()
-        .join("");
-    let program: Memory = line.split(",").map(|s| s.parse().unwrap()).collect();
-
-    let part1 = (0..5)
-        .permutations(5)
-        .map(|sequence| amplify(&program, sequence))
-        .max()
-        .expect("at least one element");
-    println!("{}", part1);
-
-    let part2 = (5..10)
-        .permutations(5)
-        .map(|sequence| amplify_loop(&program, sequence))
-        .max()
-        .expect("at least one element");
-    println!("{}", part2);
-}
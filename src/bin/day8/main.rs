@@ -1,29 +1,78 @@
-use itertools::Itertools;
+mod space_image;
+
+use adventofcode2019::grid::DenseGrid;
+use adventofcode2019::image::{self, Frame};
+use adventofcode2019::ocr;
+use space_image::{SpaceImage, TRANSPARENT, WHITE};
 use std::env;
-use std::io::{self, BufRead};
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter};
+
+const USAGE: &str = "usage: WIDTH HEIGHT [--transparent D] [--stats] [--colour] \
+                     [--ppm PATH] [--pgm PATH] [--scale N]";
 
-fn format_layer(layer: &str, width: usize) {
-    for line in &layer.chars().chunks(width) {
+fn format_layer(frame: &Frame) {
+    for line in frame.pixels.chunks(frame.width) {
         print!("  ");
-        for c in line {
-            if c == '1' {
+        for &d in line {
+            if d == WHITE {
                 print!("🎅")
             } else {
                 print!("  ");
             }
         }
-        println!("");
+        println!();
     }
 }
 
-fn count_digit(layer: &str, c: char) -> usize {
-    layer.chars().filter(|&d| d == c).count()
+/* The letters in a frame, which are drawn in white. */
+fn read(frame: &Frame) -> Result<String, ocr::Unrecognised> {
+    let rows = frame.pixels.chunks(frame.width);
+    let rows = rows.map(|row| row.iter().map(|&d| d == WHITE).collect());
+    ocr::read(&DenseGrid::from_rows(rows.collect()))
+}
+
+/* A width or height, which has to be a positive number. */
+fn dimension(arg: Option<&str>, name: &str) -> usize {
+    match arg.map(str::parse) {
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => panic!("the {} has to be a positive number\n{}", name, USAGE),
+        None => panic!("{}", USAGE),
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
-    let width: usize = args[0].parse().unwrap();
-    let height: usize = args[1].parse().unwrap();
+    let mut args = args.iter().map(String::as_str);
+    let width = dimension(args.next(), "width");
+    let height = dimension(args.next(), "height");
+    let (mut transparent, mut stats, mut colour) = (TRANSPARENT, false, false);
+    let (mut ppm, mut pgm, mut scale) = (None, None, 4);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--transparent" => {
+                transparent = args
+                    .next()
+                    .and_then(|d| d.parse().ok())
+                    .filter(|&d| d <= TRANSPARENT)
+                    .expect("--transparent 0|1|2")
+            }
+            "--stats" => stats = true,
+            "--colour" => colour = true,
+            "--ppm" => ppm = Some(args.next().expect("--ppm PATH")),
+            "--pgm" => pgm = Some(args.next().expect("--pgm PATH")),
+            "--scale" => scale = args.next().and_then(|n| n.parse().ok()).expect("--scale N"),
+            _ => panic!("{}", USAGE),
+        }
+    }
+    let draw = |frame: &Frame| {
+        if colour {
+            print!("{}", space_image::terminal(frame));
+        } else {
+            format_layer(frame);
+        }
+    };
+
     let stdin = io::stdin();
     let handle = stdin.lock();
     let line = handle
@@ -31,29 +80,102 @@ fn main() {
         .map(|l| l.unwrap())
         .collect::<Vec<String>>()
         .join("");
-    let layers: Vec<String> = line
-        .chars()
-        .chunks(width * height)
-        .into_iter()
-        .map(|layer| layer.collect::<String>())
-        .collect();
-    let fewest_0_digits = layers
-        .iter()
-        .min_by_key(|layer| count_digit(layer, '0'))
-        .unwrap();
-    format_layer(fewest_0_digits, width);
-    println!(
-        "{}",
-        count_digit(fewest_0_digits, '1') * count_digit(fewest_0_digits, '2')
-    );
-
-    let zero: String = "2".repeat(width * height);
-    let composited = layers.iter().fold(zero, |upper, lower| {
-        upper
-            .chars()
-            .zip_eq(lower.chars())
-            .map(|(c, d)| if c == '2' { d } else { c })
-            .collect::<String>()
-    });
-    format_layer(&composited, width);
+    let image = SpaceImage::new(width, height, &line).unwrap_or_else(|e| panic!("{}", e));
+    if stats {
+        println!("layer     0     1     2");
+        for (i, layer) in image.stats().iter().enumerate() {
+            let [zeros, ones, twos] = layer.counts;
+            println!("{:>5} {:>5} {:>5} {:>5}", i, zeros, ones, twos);
+        }
+    }
+    let (fewest_0_digits, checksum) = image.checksum();
+    draw(&image.layer(fewest_0_digits));
+    println!("{}", checksum);
+
+    let composited = image.composite(transparent);
+    draw(&composited);
+    match read(&composited) {
+        Ok(text) => println!("{}", text),
+        Err(e) => println!("{}", e),
+    }
+
+    let frame = composited.scaled(scale);
+    if let Some(path) = ppm {
+        let out = File::create(path).map(BufWriter::new);
+        out.and_then(|out| image::write_ppm(out, &frame, &space_image::PALETTE))
+            .expect(path);
+    }
+    if let Some(path) = pgm {
+        let out = File::create(path).map(BufWriter::new);
+        out.and_then(|out| image::write_pgm(out, &frame, &space_image::GREYS))
+            .expect(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::data_file;
+
+    fn input() -> String {
+        data_file("input-day8")
+    }
+
+    #[test]
+    fn test_image() {
+        let image = SpaceImage::new(25, 6, &input()).unwrap();
+        assert_eq!(image.checksum().1, 1848);
+        for stats in image.stats() {
+            assert_eq!(stats.counts.iter().sum::<usize>(), 25 * 6);
+        }
+        assert_eq!(read(&image.composite(TRANSPARENT)), Ok("FGJUZ".to_string()));
+
+        /* The example from the puzzle. */
+        let image = SpaceImage::new(2, 2, "0222112222120000\n").unwrap();
+        assert_eq!(image.layers.len(), 4);
+        assert_eq!(image.composite(TRANSPARENT).pixels, [0, 1, 1, 0]);
+        assert_eq!(image.composite(0).pixels, [1, 2, 2, 2]);
+        assert_eq!(image.stats()[0].counts, [1, 0, 3]);
+        assert_eq!(
+            space_image::terminal(&image.composite(TRANSPARENT)),
+            "\x1b[40m  \x1b[47m  \x1b[0m\n\x1b[47m  \x1b[40m  \x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for (width, height, digits, error) in &[
+            (2, 2, "01201", "5 digits isn't a whole number of 2×2 layers"),
+            (2, 2, "", "0 digits isn't a whole number of 2×2 layers"),
+            (2, 2, "0130", "'3' at 2 isn't 0, 1 or 2"),
+            (0, 2, "01", "a 0×2 frame is empty"),
+            (
+                usize::MAX,
+                2,
+                "01",
+                "a 18446744073709551615×2 frame is too big",
+            ),
+        ] {
+            let e = SpaceImage::new(*width, *height, digits).unwrap_err();
+            assert_eq!(e.to_string(), *error);
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let digits = concat!(
+            ".##..####.",
+            "#..#.#....",
+            "#..#.###..",
+            "####.#....",
+            "#..#.#....",
+            "#..#.####.",
+        )
+        .replace('.', "0")
+        .replace('#', "1");
+        let image = SpaceImage::new(10, 6, &digits).unwrap();
+        assert_eq!(read(&image.layer(0)), Ok("AE".to_string()));
+        let image = SpaceImage::new(10, 6, &digits.replacen('1', "0", 1)).unwrap();
+        assert_eq!(read(&image.layer(0)).unwrap_err().text, "?E");
+    }
 }
@@ -0,0 +1,129 @@
+use adventofcode2019::image::{Frame, Rgb};
+use std::io;
+
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+/* Black and white as they are, and see-through as magenta, so it shows. */
+pub const PALETTE: [Rgb; 3] = [[0, 0, 0], [255, 255, 255], [255, 0, 255]];
+pub const GREYS: [u8; 3] = [0, 255, 128];
+
+/* An image in the Space Image Format: layers of digits, each a frame of
+ * width × height, with the first layer in front.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceImage {
+    pub width: usize,
+    pub height: usize,
+    pub layers: Vec<Vec<u8>>,
+}
+
+/* How many of each digit a layer has. */
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub counts: [usize; 3],
+}
+
+impl Stats {
+    pub fn of(layer: &[u8]) -> Self {
+        let mut stats = Self::default();
+        for &d in layer {
+            stats.counts[d as usize] += 1;
+        }
+        stats
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl SpaceImage {
+    pub fn new(width: usize, height: usize, digits: &str) -> io::Result<Self> {
+        let size = width
+            .checked_mul(height)
+            .ok_or_else(|| invalid(format!("a {}×{} frame is too big", width, height)))?;
+        if size == 0 {
+            return Err(invalid(format!("a {}×{} frame is empty", width, height)));
+        }
+        let digits = digits
+            .trim()
+            .chars()
+            .enumerate()
+            .map(|(i, c)| match c.to_digit(10) {
+                Some(d) if d <= TRANSPARENT as u32 => Ok(d as u8),
+                _ => Err(invalid(format!("{:?} at {} isn't 0, 1 or 2", c, i))),
+            })
+            .collect::<io::Result<Vec<u8>>>()?;
+        if digits.is_empty() || digits.len() % size != 0 {
+            return Err(invalid(format!(
+                "{} digits isn't a whole number of {}×{} layers",
+                digits.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            layers: digits.chunks(size).map(<[u8]>::to_vec).collect(),
+        })
+    }
+
+    pub fn stats(&self) -> Vec<Stats> {
+        self.layers.iter().map(|layer| Stats::of(layer)).collect()
+    }
+
+    /* The layer with the fewest 0s, and how many 1s times how many 2s it
+     * has.
+     */
+    pub fn checksum(&self) -> (usize, usize) {
+        let (i, stats) = self
+            .stats()
+            .into_iter()
+            .enumerate()
+            .min_by_key(|(_, stats)| stats.counts[0])
+            .expect("there's always a layer");
+        (i, stats.counts[1] * stats.counts[2])
+    }
+
+    pub fn layer(&self, i: usize) -> Frame {
+        Frame {
+            width: self.width,
+            height: self.height,
+            pixels: self.layers[i].clone(),
+        }
+    }
+
+    /* Each pixel is the first one in front to the back which isn't the
+     * transparent digit, or that digit if they all are.
+     */
+    pub fn composite(&self, transparent: u8) -> Frame {
+        let mut frame = self.layer(0);
+        for layer in &self.layers[1..] {
+            for (pixel, &d) in frame.pixels.iter_mut().zip(layer) {
+                if *pixel == transparent {
+                    *pixel = d;
+                }
+            }
+        }
+        frame
+    }
+}
+
+/* Draws a frame with a coloured block for each pixel. */
+pub fn terminal(frame: &Frame) -> String {
+    let mut out = String::new();
+    for row in frame.pixels.chunks(frame.width) {
+        for &d in row {
+            out += match d {
+                BLACK => "\x1b[40m  ",
+                WHITE => "\x1b[47m  ",
+                _ => "\x1b[49m  ",
+            };
+        }
+        out += "\x1b[0m\n";
+    }
+    out
+}
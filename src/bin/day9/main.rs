@@ -117,6 +117,23 @@ impl VM {
     }
 }
 
+fn main() {
+    let stdin = io::stdin();
+    let handle = stdin.lock();
+    let line = handle
+        .lines()
+        .map(|l| l.unwrap())
+        .collect::<Vec<String>>()
+        .join("");
+    let program: Memory = line.split(",").map(|s| s.parse().unwrap()).collect();
+    for i in 1..3 {
+        let input = IO::from(vec![i]);
+        let mut vm = VM::new(&program, input);
+        vm.run();
+        println!("part {}: {:?}", i, vm.output);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,7 +150,7 @@ mod tests {
     }
 
     fn run_with_input(program: &Memory, input: IO) -> (Memory, IO) {
-        let mut vm = VM::new(&program, input);
+        let mut vm = VM::new(program, input);
         vm.run();
 
         (vm.memory, vm.output)
@@ -174,20 +191,3 @@ mod tests {
         assert_eq!(output, vec![program[1]]);
     }
 }
-
-fn main() {
-    let stdin = io::stdin();
-    let handle = stdin.lock();
-    let line = handle
-        .lines()
-        .map(|l| l.unwrap())
-        .collect::<Vec<String>>()
-        .join("");
-    let program: Memory = line.split(",").map(|s| s.parse().unwrap()).collect();
-    for i in 1..3 {
-        let input = IO::from(vec![i]);
-        let mut vm = VM::new(&program, input);
-        vm.run();
-        println!("part {}: {:?}", i, vm.output);
-    }
-}
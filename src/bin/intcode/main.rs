@@ -0,0 +1,263 @@
+extern crate adventofcode2019;
+
+use adventofcode2019::analysis::Analysis;
+use adventofcode2019::cheat::{Filter, Search};
+use adventofcode2019::decompile::decompile;
+use adventofcode2019::dump::{self, Snapshot};
+use adventofcode2019::observe::{Coverage, Observer, Profiler, Tracer};
+use adventofcode2019::optimize::{optimize, verify};
+use adventofcode2019::strict::Validation;
+use adventofcode2019::workload::WORKLOADS;
+use adventofcode2019::{program_from_stdin, program_from_str, VM};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+
+fn format_program(program: &[i64]) -> String {
+    program
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/* Optimizes each program in the data directory and checks it still does the
+ * same thing.
+ */
+fn verify_data(dir: &str) -> bool {
+    let mut ok = true;
+    for w in WORKLOADS {
+        let program = w.program_in(dir).expect(w.name);
+        let (optimized, stats) = optimize(&program);
+        match verify(&program, &optimized, w.driver) {
+            Ok((before, after)) => println!(
+                "{:<12} {:<20}: ok, {} -> {} steps ({})",
+                w.name, w.description, before, after, stats
+            ),
+            Err(e) => {
+                println!("{:<12} {:<20}: MISMATCH: {}", w.name, w.description, e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/* Runs the program in strict mode on the given inputs. */
+fn check(inputs: &[&str]) -> bool {
+    let mut vm = VM::new(&program_from_stdin());
+    vm.set_validation(Validation::Strict);
+    vm.input
+        .extend(inputs.iter().map(|x| x.parse::<i64>().expect(x)));
+    match vm.try_run() {
+        Ok(state) => {
+            println!(
+                "ok: {:?} after {} steps, output {:?}",
+                state,
+                vm.steps(),
+                vm.output
+            );
+            true
+        }
+        Err(violation) => {
+            println!("{}", violation);
+            false
+        }
+    }
+}
+
+/* Runs the program on the given inputs with an observer installed, then
+ * hands it back.
+ */
+fn observed<T: Observer>(program: &[i64], inputs: &[&str], observer: T) -> T {
+    let mut vm = VM::new(&program.to_vec());
+    vm.observe(observer);
+    vm.input
+        .extend(inputs.iter().map(|x| x.parse::<i64>().expect(x)));
+    let state = vm.run();
+    eprintln!(
+        "{:?} after {} steps, output {:?}",
+        state,
+        vm.steps(),
+        vm.output
+    );
+    vm.take_observer().unwrap()
+}
+
+/* Prints memory after running at most the given number of instructions. */
+fn snapshot(steps: &str, inputs: &[&str]) {
+    let mut vm = VM::new(&program_from_stdin());
+    vm.input
+        .extend(inputs.iter().map(|x| x.parse::<i64>().expect(x)));
+    let state = vm.run_steps(steps.parse().expect(steps));
+    eprintln!("{:?} after {} steps", state, vm.steps());
+    println!("{}", Snapshot::of(&vm));
+}
+
+/* Dumps a snapshot file, compared against an earlier one if given. */
+fn dump_files(args: &[&str]) {
+    let mut options = dump::Options::default();
+    let mut files = Vec::new();
+    for arg in args {
+        match *arg {
+            "--changed" => options.changed_only = true,
+            "--colour" => options.colour = true,
+            path => files.push(Snapshot::load(path).expect(path)),
+        }
+    }
+    match files.as_slice() {
+        [snapshot] => print!("{}", dump::dump(snapshot, None, &options)),
+        [previous, snapshot] => print!("{}", dump::dump(snapshot, Some(previous), &options)),
+        _ => panic!("usage: intcode dump [--changed] [--colour] [BEFORE] SNAPSHOT"),
+    }
+}
+
+fn show(search: &Search, vm: &VM) {
+    let values = search.values(vm);
+    if values.len() <= 20 {
+        for (address, value) in values {
+            println!("{:>6}: {}", address, value);
+        }
+    }
+}
+
+/* Carries out one command to look for, or change, cells in the program's
+ * memory: "run", "in VALUE...", "new" to start a search, a filter ("eq
+ * VALUE", "same", "changed", "inc" or "dec"), "list", "peek ADDRESS" and
+ * "poke ADDRESS VALUE".
+ */
+fn command(vm: &mut VM, search: &mut Search, line: &str) -> Result<(), String> {
+    let number = |x: &str| x.parse::<i64>().map_err(|_| format!("bad number {:?}", x));
+    let len = vm.memory().len();
+    let address = |x: &str| {
+        number(x).and_then(|a| {
+            if 0 <= a && (a as usize) < len {
+                Ok(a as usize)
+            } else {
+                Err(format!("address {} out of range", a))
+            }
+        })
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(()),
+        ["run"] => {
+            let state = vm.run();
+            println!("{:?}, output {:?}", state, vm.drain_output());
+            Ok(())
+        }
+        ["in", values @ ..] => values
+            .iter()
+            .map(|x| number(x))
+            .collect::<Result<Vec<i64>, String>>()
+            .map(|values| vm.input.extend(values)),
+        ["new"] => {
+            *search = Search::new(vm);
+            Ok(())
+        }
+        ["list"] => {
+            show(search, vm);
+            Ok(())
+        }
+        ["peek", a] => address(a).map(|a| println!("{}", vm.memory().get(a))),
+        ["poke", a, value] => address(a).and_then(|a| {
+            vm.poke(a, number(value)?);
+            Ok(())
+        }),
+        _ => line.parse::<Filter>().map(|filter| {
+            println!("{} candidates", search.filter(vm, filter));
+            show(search, vm);
+        }),
+    }
+}
+
+/* Runs commands from stdin against the program until it runs out of them,
+ * reporting any which can't be carried out.
+ */
+fn cheat(path: &str, inputs: &[&str]) {
+    let program = program_from_str(&fs::read_to_string(path).expect(path));
+    let mut vm = VM::new(&program);
+    vm.input
+        .extend(inputs.iter().map(|x| x.parse::<i64>().expect(x)));
+    let mut search = Search::new(&vm);
+    for line in io::stdin().lock().lines() {
+        if let Err(e) = command(&mut vm, &mut search, &line.unwrap()) {
+            println!("{}", e);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["disasm"] => print!("{}", Analysis::new(&program_from_stdin()).disassemble()),
+        ["check", inputs @ ..] => {
+            if !check(inputs) {
+                std::process::exit(1);
+            }
+        }
+        ["trace", inputs @ ..] => {
+            observed(&program_from_stdin(), inputs, Tracer::new(std::io::stdout()));
+        }
+        ["profile", inputs @ ..] => {
+            let program = program_from_stdin();
+            let profiler = observed(&program, inputs, Profiler::new());
+            print!("{}", profiler.report(&program, 20));
+        }
+        ["coverage", inputs @ ..] => {
+            let program = program_from_stdin();
+            let coverage = observed(&program, inputs, Coverage::new());
+            print!("{}", coverage.report(&program));
+        }
+        ["snapshot", steps, inputs @ ..] => snapshot(steps, inputs),
+        ["dump", args @ ..] => dump_files(args),
+        ["cheat", path, inputs @ ..] => cheat(path, inputs),
+        ["decompile"] => print!("{}", decompile(&program_from_stdin())),
+        ["opt"] => {
+            let (optimized, stats) = optimize(&program_from_stdin());
+            eprintln!("{}", stats);
+            println!("{}", format_program(&optimized));
+        }
+        ["opt", "--verify"] => {
+            if !verify_data("data") {
+                std::process::exit(1);
+            }
+        }
+        ["opt", "--verify", dir] => {
+            if !verify_data(dir) {
+                std::process::exit(1);
+            }
+        }
+        _ => panic!("usage: intcode disasm|decompile|opt|check|trace|profile|coverage [INPUT...] < program, intcode snapshot STEPS [INPUT...] < program, intcode dump [--changed] [--colour] [BEFORE] SNAPSHOT, intcode cheat PROGRAM [INPUT...], or intcode opt --verify [DIR]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adventofcode2019::data_program;
+
+    #[test]
+    fn test_bad_address() {
+        let mut vm = VM::new(&data_program("input-day9"));
+        let mut search = Search::new(&vm);
+        let len = vm.memory().len();
+        assert_eq!(
+            command(&mut vm, &mut search, "peek -1"),
+            Err(String::from("address -1 out of range"))
+        );
+        assert_eq!(
+            command(&mut vm, &mut search, "poke 99999999 1"),
+            Err(String::from("address 99999999 out of range"))
+        );
+        assert_eq!(
+            command(&mut vm, &mut search, &format!("peek {}", len)),
+            Err(format!("address {} out of range", len))
+        );
+        /* The session carries on with good commands afterwards. */
+        assert_eq!(command(&mut vm, &mut search, "poke 0 7"), Ok(()));
+        assert_eq!(vm.memory().get(0), 7);
+        assert_eq!(command(&mut vm, &mut search, "eq 7"), Ok(()));
+    }
+}
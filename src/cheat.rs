@@ -0,0 +1,198 @@
+use crate::dump::Snapshot;
+use crate::VM;
+use std::fmt;
+
+/* How a cell must have behaved since the last look to remain a candidate. */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Equal(i64),
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn keeps(self, old: i64, new: i64) -> bool {
+        match self {
+            Filter::Equal(value) => new == value,
+            Filter::Unchanged => new == old,
+            Filter::Changed => new != old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Equal(value) => write!(f, "eq {}", value),
+            Filter::Unchanged => write!(f, "same"),
+            Filter::Changed => write!(f, "changed"),
+            Filter::Increased => write!(f, "inc"),
+            Filter::Decreased => write!(f, "dec"),
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["eq", value] => value
+                .parse()
+                .map(Filter::Equal)
+                .map_err(|_| format!("bad value {:?}", value)),
+            ["same"] => Ok(Filter::Unchanged),
+            ["changed"] => Ok(Filter::Changed),
+            ["inc"] => Ok(Filter::Increased),
+            ["dec"] => Ok(Filter::Decreased),
+            _ => Err(format!("unknown filter {:?}", s)),
+        }
+    }
+}
+
+/* Narrows down which cells hold some value the program keeps, such as a
+ * score or a coordinate: start with every cell, then alternately let the
+ * program run and drop the cells which didn't behave as that value did.
+ */
+#[derive(Debug, Clone)]
+pub struct Search {
+    previous: Snapshot,
+    candidates: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(vm: &VM) -> Self {
+        Self {
+            previous: Snapshot::of(vm),
+            candidates: (0..vm.memory().len()).collect(),
+        }
+    }
+
+    /* Compares the VM with how it was at the last filter (or when the
+     * search started), and returns how many candidates are left.
+     */
+    pub fn filter(&mut self, vm: &VM, filter: Filter) -> usize {
+        let snapshot = Snapshot::of(vm);
+        let previous = &self.previous;
+        self.candidates
+            .retain(|&a| filter.keeps(previous.get(a), snapshot.get(a)));
+        self.previous = snapshot;
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    /* The candidates with their current values. */
+    pub fn values(&self, vm: &VM) -> Vec<(usize, i64)> {
+        self.candidates
+            .iter()
+            .map(|&a| (a, vm.memory().get(a)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_program, State};
+    use std::cmp::Ordering;
+
+    fn day13() -> VM {
+        let mut program = data_program("day13-input");
+        program[0] = 2;
+        VM::new(&program)
+    }
+
+    fn frame(vm: &mut VM, joystick: i64) {
+        vm.input.push_back(joystick);
+        assert_eq!(vm.run(), State::NeedInput);
+        vm.drain_output();
+    }
+
+    #[test]
+    fn test_ball() {
+        /* Follows the ball with nothing but what's seen on screen, steering
+         * the paddle after it to keep the game going.
+         */
+        let mut vm = day13();
+        assert_eq!(vm.run(), State::NeedInput);
+        vm.drain_output();
+        let mut search = Search::new(&vm);
+        let (mut x, mut paddle): (i64, i64) = (15, 17);
+        for _ in 0..50 {
+            vm.input.push_back((x - paddle).signum());
+            assert_eq!(vm.run(), State::NeedInput);
+            let output: Vec<i64> = vm.drain_output().into_iter().collect();
+            let seen = |tile| output.chunks(3).rfind(|c| c[2] == tile).map(|c| c[0]);
+            let new = seen(4).unwrap();
+            paddle = seen(3).unwrap_or(paddle);
+            search.filter(
+                &vm,
+                match new.cmp(&x) {
+                    Ordering::Less => Filter::Decreased,
+                    Ordering::Equal => Filter::Unchanged,
+                    Ordering::Greater => Filter::Increased,
+                },
+            );
+            x = new;
+        }
+        search.filter(&vm, Filter::Equal(x));
+        /* The ball's variable, and a copy left on the stack, which starts
+         * just past the program.
+         */
+        assert_eq!(search.values(&vm), vec![(388, x), (2391, x)]);
+    }
+
+    #[test]
+    fn test_wide_paddle() {
+        /* Finds the paddle on the screen the program keeps in memory: the
+         * cell which held a paddle tile, and is empty once it moves.
+         */
+        let mut vm = day13();
+        frame(&mut vm, 0);
+        let mut search = Search::new(&vm);
+        search.filter(&vm, Filter::Equal(3));
+        frame(&mut vm, 1);
+        assert_eq!(search.filter(&vm, Filter::Equal(0)), 1);
+
+        /* Pokes paddle across the whole row and lets the game play itself. */
+        let paddle = search.candidates()[0];
+        for address in paddle - 16..paddle + 17 {
+            vm.poke(address, 3);
+        }
+        let mut score = 0;
+        loop {
+            vm.input.push_back(0);
+            let state = vm.run();
+            let output: Vec<i64> = vm.drain_output().into_iter().collect();
+            if let Some(c) = output.chunks(3).rfind(|c| c[0] == -1) {
+                score = c[2];
+            }
+            if state == State::Halted {
+                break;
+            }
+        }
+        assert_eq!(score, 15410);
+    }
+
+    #[test]
+    fn test_parse() {
+        for filter in [
+            Filter::Equal(-3),
+            Filter::Unchanged,
+            Filter::Changed,
+            Filter::Increased,
+            Filter::Decreased,
+        ] {
+            assert_eq!(filter.to_string().parse(), Ok(filter));
+        }
+        assert!("eq".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::opcodes::{OpcodeTable, Role};
+use crate::replay::Event;
+use crate::{State, VM};
+use std::sync::Arc;
+
+/* What code generated by transpile runs against: a VM, whose memory and
+ * registers compiled instructions use directly, and which interprets any
+ * instruction that wasn't compiled.
+ *
+ * Compiled code is only valid while the cells it was compiled from are
+ * unchanged.  Instructions the program is known to modify are never
+ * compiled, but writes through computed addresses can't be seen ahead of
+ * time, so they are checked as they happen; once one lands on compiled code
+ * everything is interpreted from then on.
+ */
+#[derive(Clone)]
+pub struct Host {
+    vm: VM,
+    /* The VM's table, kept here so that step() can borrow it. */
+    opcodes: Arc<OpcodeTable>,
+    code: Arc<[bool]>,
+    dirty: bool,
+}
+
+impl Host {
+    /* `compiled` lists the cells, as [start, end) ranges, which the
+     * generated code was compiled from.
+     */
+    pub fn new(program: &[i64], compiled: &[(usize, usize)]) -> Self {
+        let mut code = vec![false; program.len()];
+        for &(start, end) in compiled {
+            for cell in &mut code[start..end] {
+                *cell = true;
+            }
+        }
+        let vm = VM::new(&program.to_vec());
+        Self {
+            opcodes: vm.opcodes.clone(),
+            vm,
+            code: code.into(),
+            dirty: false,
+        }
+    }
+
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    /* Whether compiled code has been overwritten. */
+    pub fn dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /* Whether to leave everything to the interpreter: once compiled code
+     * has been overwritten, and while the VM has observers or validation
+     * turned on, as compiled code neither notifies nor checks anything.
+     */
+    pub fn interpreted(&self) -> bool {
+        self.dirty || !self.vm.observers.is_empty() || self.vm.checker.is_some()
+    }
+
+    fn touch(&mut self, address: usize) -> bool {
+        if self.code.get(address) == Some(&true) {
+            self.dirty = true;
+        }
+        self.dirty
+    }
+
+    #[inline]
+    pub fn ip(&self) -> usize {
+        self.vm.ip
+    }
+
+    #[inline]
+    pub fn jump(&mut self, ip: usize) {
+        self.vm.ip = ip;
+    }
+
+    #[inline]
+    pub fn tick(&mut self) {
+        self.vm.steps += 1;
+        self.vm.stopped = None;
+    }
+
+    #[inline]
+    pub fn get(&self, address: usize) -> i64 {
+        self.vm.memory.get(address)
+    }
+
+    #[inline]
+    pub fn get_relative(&self, offset: i64) -> i64 {
+        self.vm
+            .memory
+            .get((self.vm.relative_base + offset) as usize)
+    }
+
+    /* Only for addresses known not to hold compiled code. */
+    #[inline]
+    pub fn set(&mut self, address: usize, value: i64) {
+        self.vm.memory.set(address, value);
+    }
+
+    /* Returns true if this overwrote compiled code. */
+    #[inline]
+    pub fn set_relative(&mut self, offset: i64, value: i64) -> bool {
+        let address = (self.vm.relative_base + offset) as usize;
+        self.vm.memory.set(address, value);
+        self.touch(address)
+    }
+
+    #[inline]
+    pub fn adjust_base(&mut self, offset: i64) {
+        self.vm.relative_base += offset;
+    }
+
+    pub fn input(&mut self) -> Option<i64> {
+        let value = self.vm.input.pop_front()?;
+        let step = self.vm.steps;
+        self.vm.log(Event::Input { step, value });
+        Some(value)
+    }
+
+    pub fn output(&mut self, value: i64) {
+        self.vm.output.push_back(value);
+        let step = self.vm.steps;
+        self.vm.log(Event::Output { step, value });
+    }
+
+    /* Interprets the instruction at ip. */
+    pub fn step(&mut self) -> Option<State> {
+        let code = self.vm.memory.get(self.vm.ip) % 100;
+        if let Some(opcode) = self.opcodes.get(code) {
+            for (i, role) in opcode.roles.iter().enumerate() {
+                if *role == Role::Write {
+                    let address = self.vm.address(i as u32 + 1);
+                    if self.code.get(address) == Some(&true) {
+                        self.dirty = true;
+                    }
+                }
+            }
+        }
+        self.vm.step(&self.opcodes)
+    }
+
+    /* Runs the interpreter alone, once compiled code can't be trusted. */
+    pub fn interpret(&mut self) -> State {
+        self.vm.run()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty() {
+        /* Pretends 6..9 was compiled, then overwrites it from interpreted
+         * code.
+         */
+        let mut host = Host::new(&[1101, 2, 3, 7, 1101, 0, 0, 0, 99], &[(6, 9)]);
+        assert_eq!(host.step(), None);
+        assert!(host.dirty());
+
+        let mut host = Host::new(&[1101, 2, 3, 9, 99, 0, 0, 0, 0, 0], &[(4, 5)]);
+        assert_eq!(host.step(), None);
+        assert!(!host.dirty());
+        host.adjust_base(2);
+        assert!(host.set_relative(2, 1));
+        assert_eq!(host.get(4), 1);
+    }
+}
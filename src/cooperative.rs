@@ -0,0 +1,267 @@
+use crate::{State, VM};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+/* Many VMs on one thread: each runs as a future which, when its program
+ * wants input, waits on a channel instead of blocking, and the executor
+ * gets on with another machine.  A machine only yields while waiting for
+ * input, so one which never asks for any keeps the thread to itself.
+ */
+
+struct Inner {
+    queue: VecDeque<i64>,
+    waker: Option<Waker>,
+    senders: usize,
+}
+
+/* An unbounded channel of values, closed once every sender is dropped. */
+pub fn channel() -> (Sender, Receiver) {
+    let inner = Rc::new(RefCell::new(Inner {
+        queue: VecDeque::new(),
+        waker: None,
+        senders: 1,
+    }));
+    (Sender(inner.clone()), Receiver(inner))
+}
+
+pub struct Sender(Rc<RefCell<Inner>>);
+
+impl Sender {
+    pub fn send(&self, value: i64) {
+        let mut inner = self.0.borrow_mut();
+        inner.queue.push_back(value);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.0.borrow_mut().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.senders -= 1;
+        if inner.senders == 0 {
+            if let Some(waker) = inner.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Receiver(Rc<RefCell<Inner>>);
+
+impl Receiver {
+    /* The next value, or None once the channel is empty and closed. */
+    pub fn recv(&self) -> Recv<'_> {
+        Recv(self)
+    }
+
+    pub fn try_recv(&self) -> Option<i64> {
+        self.0.borrow_mut().queue.pop_front()
+    }
+}
+
+pub struct Recv<'a>(&'a Receiver);
+
+impl<'a> Future for Recv<'a> {
+    type Output = Option<i64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<i64>> {
+        let mut inner = (self.0).0.borrow_mut();
+        if let Some(value) = inner.queue.pop_front() {
+            Poll::Ready(Some(value))
+        } else if inner.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/* Runs the VM, feeding it from input and sending its output on, until it
+ * halts or wants input after the input channel has closed.  Dropping the
+ * output sender on return closes the channel for whoever reads it.
+ */
+pub async fn run(mut vm: VM, input: Receiver, output: Sender) -> VM {
+    loop {
+        let state = vm.run();
+        for value in vm.drain_output() {
+            output.send(value);
+        }
+        match state {
+            State::Halted => return vm,
+            State::NeedInput => match input.recv().await {
+                Some(value) => vm.input.push_back(value),
+                None => return vm,
+            },
+        }
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+/* What a spawned future returned, once it has finished. */
+pub struct Handle<T>(Rc<RefCell<Option<T>>>);
+
+impl<T> Handle<T> {
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+}
+
+/* A single-threaded executor, polling tasks in the order they were woken. */
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Task>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&mut self, future: F) -> Handle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let result = Rc::new(RefCell::new(None));
+        let slot = result.clone();
+        self.tasks.push(Some(Box::pin(async move {
+            let value = future.await;
+            *slot.borrow_mut() = Some(value);
+        })));
+        self.ready.lock().unwrap().push_back(self.tasks.len() - 1);
+        Handle(result)
+    }
+
+    /* Runs until no task can make progress.  Returns how many are left
+     * waiting, which is 0 unless they deadlocked.
+     */
+    pub fn run(&mut self) -> usize {
+        loop {
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let task = match &mut self.tasks[id] {
+                Some(task) => task,
+                /* Woken after it finished. */
+                None => continue,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: self.ready.clone(),
+            }));
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready()
+            {
+                self.tasks[id] = None;
+            }
+        }
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+/* Runs a future, and anything it needs, on a fresh executor. */
+pub fn block_on<F: Future + 'static>(future: F) -> F::Output {
+    let mut executor = Executor::new();
+    let handle = executor.spawn(future);
+    executor.run();
+    handle.take().expect("the future deadlocked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Reads a value and outputs it plus one, forever. */
+    const INCREMENT: [i64; 12] = [3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0];
+
+    #[test]
+    fn test_ring() {
+        const MACHINES: usize = 50;
+        const LAPS: i64 = 3;
+        /* Reads a value and outputs it plus one, until it reads 150. */
+        let relay = vec![
+            3, 19, 1008, 19, 150, 20, 1005, 20, 18, 1001, 19, 1, 19, 4, 19, 1105, 1, 0, 99, 0, 0,
+        ];
+        assert_eq!(relay[4], MACHINES as i64 * LAPS);
+
+        /* Each machine's output is the next one's input, and the last's is
+         * the first's.
+         */
+        let (mut senders, receivers): (Vec<Sender>, Vec<Receiver>) =
+            (0..MACHINES).map(|_| channel()).unzip();
+        senders.rotate_left(1);
+        let mut executor = Executor::new();
+        let mut handles = Vec::new();
+        for (i, (input, output)) in receivers.into_iter().zip(senders).enumerate() {
+            let mut vm = VM::new(&relay);
+            if i == 0 {
+                vm.input.push_back(0);
+            }
+            handles.push(executor.spawn(run(vm, input, output)));
+        }
+
+        assert_eq!(executor.run(), 0);
+        let vms: Vec<VM> = handles.iter().map(|h| h.take().unwrap()).collect();
+        /* The value comes back to the first machine after going round
+         * LAPS times, and it stops; the rest then find their input closed.
+         */
+        assert_eq!(vms[0].memory().get(19), MACHINES as i64 * LAPS);
+        assert_eq!(vms[0].steps(), 6 * LAPS as u64 + 3);
+        for vm in &vms[1..] {
+            assert_eq!(vm.steps(), 6 * LAPS as u64);
+        }
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut executor = Executor::new();
+        let (sender, receiver) = channel();
+        let (output, _) = channel();
+        executor.spawn(run(VM::new(&INCREMENT.to_vec()), receiver, output));
+        /* Nothing is ever sent, but the channel stays open. */
+        assert_eq!(executor.run(), 1);
+        drop(sender);
+        assert_eq!(executor.run(), 0);
+    }
+
+    #[test]
+    fn test_block_on() {
+        let (sender, receiver) = channel();
+        let (output, results) = channel();
+        sender.send(41);
+        drop(sender);
+        let vm = block_on(run(VM::new(&INCREMENT.to_vec()), receiver, output));
+        assert_eq!(vm.steps(), 4);
+        assert_eq!(results.try_recv(), Some(42));
+    }
+}
@@ -0,0 +1,843 @@
+use crate::analysis::{Analysis, Instruction, Mode, Op, Param};
+use std::collections::{BTreeMap, BTreeSet};
+
+/* A function found by its call sites.  Compiled Intcode calls a function by
+ * storing its arguments at [rb + 1], [rb + 2]..., the return address at
+ * [rb + 0], and jumping to it; the function starts with `arb N`, so the
+ * return address ends up at [rb - N] and the arguments just above it, and
+ * returns with `arb -N` and a jump through [rb + 0].  Results are passed back
+ * in the first argument's slot.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    pub end: usize,
+    pub frame: i64,
+    pub params: usize,
+    pub returns: bool,
+}
+
+impl Function {
+    pub fn name(&self) -> String {
+        if self.entry == 0 {
+            String::from("main")
+        } else {
+            format!("f{}", self.entry)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cond {
+    lhs: String,
+    op: &'static str,
+    rhs: String,
+}
+
+impl Cond {
+    fn negate(&self) -> Self {
+        let op = match self.op {
+            "<" => ">=",
+            ">=" => "<",
+            "==" => "!=",
+            _ => "==",
+        };
+        Cond {
+            lhs: self.lhs.clone(),
+            op,
+            rhs: self.rhs.clone(),
+        }
+    }
+
+    fn render(&self) -> String {
+        format!("{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Statement(String),
+    Jump(Option<Cond>, usize),
+    Return,
+    /* The `arb N` a function starts with. */
+    Prologue,
+}
+
+enum Line {
+    Label(usize),
+    Text(usize, String),
+}
+
+/* Turns a program back into structured pseudocode: functions with
+ * parameters and locals, loops, if/else, and named memory cells.  Control
+ * flow which doesn't fit is left as gotos.
+ */
+pub struct Decompiler<'a> {
+    analysis: &'a Analysis,
+    pub functions: BTreeMap<usize, Function>,
+    /* Statements keyed by the address of their first instruction, with the
+     * address following them.
+     */
+    items: BTreeMap<usize, (Item, usize)>,
+    globals: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    lines: Vec<Line>,
+    /* The function being emitted, and the (header, exit) of each loop
+     * we're inside.
+     */
+    function: Function,
+    loops: Vec<(usize, usize)>,
+}
+
+/* The function a call at this address jumps to, and the return address. */
+fn call(analysis: &Analysis, address: usize) -> Option<(usize, usize)> {
+    let store = analysis.instructions.get(&address)?;
+    let jump = analysis.instructions.get(&store.next())?;
+    let ret = store.constant_result()?;
+    let target = jump.params.get(1)?.constant()?;
+    if store.params.get(2) == Some(&relative(0))
+        && jump.taken() == Some(true)
+        && ret == jump.next() as i64
+        && target >= 0
+    {
+        Some((target as usize, ret as usize))
+    } else {
+        None
+    }
+}
+
+fn relative(offset: i64) -> Param {
+    Param {
+        mode: Mode::Relative,
+        value: offset,
+    }
+}
+
+/* The argument slot an instruction stores to ahead of a call, if any. */
+fn argument(instruction: &Instruction) -> Option<i64> {
+    let out = instruction.params.get(instruction.op.output()?)?;
+    let reads_stack = instruction
+        .params
+        .iter()
+        .take(2)
+        .any(|p| p.mode == Mode::Relative && p.value > 0);
+    match out.mode {
+        Mode::Relative if out.value > 0 && !reads_stack && instruction.op != Op::In => {
+            Some(out.value)
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Decompiler<'a> {
+    pub fn new(analysis: &'a Analysis) -> Self {
+        let mut decompiler = Self {
+            analysis,
+            functions: BTreeMap::new(),
+            items: BTreeMap::new(),
+            globals: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            lines: Vec::new(),
+            function: Function::default(),
+            loops: Vec::new(),
+        };
+        decompiler.find_functions();
+        decompiler
+    }
+
+    fn find_functions(&mut self) {
+        let analysis = self.analysis;
+        let leaders = analysis.leaders();
+        let mut entries: BTreeMap<usize, usize> = BTreeMap::new();
+        entries.insert(0, 0);
+        for &address in analysis.instructions.keys() {
+            if let Some((target, _)) = call(analysis, address) {
+                if analysis.instructions.contains_key(&target) {
+                    let args = self.arguments(address, &leaders);
+                    let args = args.keys().next_back().map_or(0, |&slot| slot as usize);
+                    let params = entries.entry(target).or_insert(0);
+                    *params = (*params).max(args);
+                }
+            }
+        }
+
+        let code_end = analysis
+            .instructions
+            .values()
+            .next_back()
+            .map_or(0, |i| i.next());
+        let starts: Vec<usize> = entries.keys().cloned().collect();
+        for (i, &entry) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).cloned().unwrap_or(code_end);
+            let frame = match &analysis.instructions[&entry] {
+                i if entry != 0 && i.op == Op::AdjustBase => i.params[0].constant().unwrap_or(0),
+                _ => 0,
+            };
+            let params = entries[&entry].min((frame - 1).max(0) as usize);
+            let first = relative(1 - frame);
+            let returns = params > 0
+                && analysis
+                    .instructions
+                    .range(entry..end)
+                    .any(|(_, i)| i.op.output().map(|o| i.params[o]) == Some(first));
+            self.functions.insert(
+                entry,
+                Function {
+                    entry,
+                    end,
+                    frame,
+                    params,
+                    returns,
+                },
+            );
+        }
+    }
+
+    /* The instructions storing arguments immediately before the call at this
+     * address, by slot.
+     */
+    fn arguments(&self, address: usize, leaders: &BTreeSet<usize>) -> BTreeMap<i64, usize> {
+        let mut args = BTreeMap::new();
+        let mut next = address;
+        for (&a, instruction) in self.analysis.instructions.range(..address).rev() {
+            if instruction.next() != next || leaders.contains(&next) {
+                break;
+            }
+            match argument(instruction) {
+                Some(slot) if !args.contains_key(&slot) => {
+                    args.insert(slot, a);
+                }
+                _ => break,
+            }
+            next = a;
+        }
+        args
+    }
+
+    fn function_at(&self, address: usize) -> &Function {
+        self.functions.range(..=address).next_back().unwrap().1
+    }
+
+    fn param(&mut self, function: &Function, instruction: &Instruction, i: usize) -> String {
+        let param = instruction.params[i];
+        let cell = instruction.address + 1 + i;
+        if self.analysis.writes.contains(&cell) {
+            /* The operand itself is computed, so it's a pointer. */
+            self.globals.insert(cell);
+            return match param.mode {
+                Mode::Immediate => format!("g{}", cell),
+                Mode::Position => format!("mem[g{}]", cell),
+                Mode::Relative => format!("mem[rb + g{}]", cell),
+            };
+        }
+        match param.mode {
+            Mode::Immediate => param.value.to_string(),
+            Mode::Position if param.value < 0 => format!("mem[{}]", param.value),
+            Mode::Position => {
+                self.globals.insert(param.value as usize);
+                format!("g{}", param.value)
+            }
+            Mode::Relative => {
+                let slot = function.frame + param.value;
+                if param.value > 0 || (function.frame == 0 && param.value == 0) {
+                    format!("t{}", param.value)
+                } else if function.frame > 0 && slot == 0 {
+                    String::from("ret")
+                } else if slot < 0 {
+                    format!("mem[rb - {}]", -param.value)
+                } else if slot as usize <= function.params {
+                    format!("arg{}", slot)
+                } else {
+                    format!("local{}", slot as usize - function.params)
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, function: &Function, instruction: &Instruction) -> String {
+        if let Some(value) = instruction.constant_result() {
+            return value.to_string();
+        }
+        let a = self.param(function, instruction, 0);
+        let b = self.param(function, instruction, 1);
+        match instruction.op {
+            Op::Add if a == "0" => b,
+            Op::Add if b == "0" => a,
+            Op::Add if b.starts_with('-') => format!("{} - {}", a, &b[1..]),
+            Op::Add => format!("{} + {}", a, b),
+            Op::Mul if a == "1" => b,
+            Op::Mul if b == "1" => a,
+            Op::Mul if a == "-1" => format!("-{}", b),
+            Op::Mul if b == "-1" => format!("-{}", a),
+            Op::Mul => format!("{} * {}", a, b),
+            Op::LessThan => format!("{} < {}", a, b),
+            _ => format!("{} == {}", a, b),
+        }
+    }
+
+    fn statement(&mut self, function: &Function, instruction: &Instruction) -> String {
+        match instruction.op {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+                let value = self.expression(function, instruction);
+                format!("{} = {}", self.param(function, instruction, 2), value)
+            }
+            Op::In => format!("{} = input()", self.param(function, instruction, 0)),
+            Op::Out => format!("output({})", self.param(function, instruction, 0)),
+            Op::AdjustBase => match instruction.params[0].constant() {
+                Some(n) if n < 0 => format!("rb -= {}", -n),
+                _ => format!("rb += {}", self.param(function, instruction, 0)),
+            },
+            Op::Halt => String::from("halt"),
+            Op::JumpIfTrue | Op::JumpIfFalse => unreachable!(),
+        }
+    }
+
+    /* The condition under which a jump is taken, or None if it always is. */
+    fn condition(
+        &mut self,
+        function: &Function,
+        jump: &Instruction,
+        compare: Option<&Instruction>,
+    ) -> Option<Cond> {
+        if jump.taken().is_some() {
+            return None;
+        }
+        let cond = match compare {
+            Some(compare) => Cond {
+                lhs: self.param(function, compare, 0),
+                op: if compare.op == Op::LessThan {
+                    "<"
+                } else {
+                    "=="
+                },
+                rhs: self.param(function, compare, 1),
+            },
+            None => Cond {
+                lhs: self.param(function, jump, 0),
+                op: "!=",
+                rhs: String::from("0"),
+            },
+        };
+        Some(if jump.op == Op::JumpIfTrue {
+            cond
+        } else {
+            cond.negate()
+        })
+    }
+
+    /* Cells only ever used to hold a comparison for the jump right after it,
+     * which can be folded into the jump's condition.
+     */
+    fn flags(&self) -> BTreeSet<usize> {
+        let analysis = self.analysis;
+        let leaders = analysis.leaders();
+        let mut flags: BTreeSet<usize> = analysis.writes.clone();
+        for instruction in analysis.instructions.values() {
+            for (i, param) in instruction.params.iter().enumerate() {
+                if param.mode != Mode::Position || instruction.op.output() == Some(i) {
+                    continue;
+                }
+                let folded = i == 0
+                    && instruction.is_jump()
+                    && !leaders.contains(&instruction.address)
+                    && analysis
+                        .instructions
+                        .range(..instruction.address)
+                        .next_back()
+                        .is_some_and(|(_, c)| {
+                            c.next() == instruction.address
+                                && matches!(c.op, Op::LessThan | Op::Equals)
+                                && c.params[2] == *param
+                        });
+                if !folded && param.value >= 0 {
+                    flags.remove(&(param.value as usize));
+                }
+            }
+            for i in instruction.cells() {
+                flags.remove(&i);
+            }
+        }
+        flags
+    }
+
+    /* The jump testing the flag this comparison sets, if it can be folded. */
+    fn folded_jump(
+        &self,
+        compare: &Instruction,
+        flags: &BTreeSet<usize>,
+        leaders: &BTreeSet<usize>,
+    ) -> Option<&'a Instruction> {
+        let flag = compare.params.get(2)?;
+        let jump = self.analysis.instructions.get(&compare.next())?;
+        let folds = matches!(compare.op, Op::LessThan | Op::Equals)
+            && flag.mode == Mode::Position
+            && flag.value >= 0
+            && flags.contains(&(flag.value as usize))
+            && jump.is_jump()
+            && jump.params[0] == *flag
+            && !leaders.contains(&jump.address);
+        if folds {
+            Some(jump)
+        } else {
+            None
+        }
+    }
+
+    fn find_items(&mut self) {
+        let analysis = self.analysis;
+        let leaders = analysis.leaders();
+        let flags = self.flags();
+        let mut skip = BTreeSet::new();
+        let mut items = BTreeMap::new();
+
+        for (&address, instruction) in &analysis.instructions {
+            if skip.contains(&address) {
+                continue;
+            }
+            let function = self.function_at(address).clone();
+            let (item, next) = if let Some((target, ret)) = call(analysis, address) {
+                let args = self.arguments(address, &leaders);
+                let callee = self.functions.get(&target).cloned();
+                let params = callee.as_ref().map_or(0, |f| f.params);
+                let mut values = Vec::new();
+                for slot in 1..=params as i64 {
+                    values.push(match args.get(&slot) {
+                        Some(&a) => {
+                            let stored = &analysis.instructions[&a];
+                            items.remove(&a);
+                            self.expression(&function, stored)
+                        }
+                        None => format!("t{}", slot),
+                    });
+                }
+                let name = callee.as_ref().map_or(format!("f{}", target), |f| f.name());
+                let call = format!("{}({})", name, values.join(", "));
+                let start = args.values().next().cloned().unwrap_or(address);
+                let text = match callee {
+                    Some(f) if f.returns => format!("t1 = {}", call),
+                    _ => call,
+                };
+                skip.insert(instruction.next());
+                items.insert(start, (Item::Statement(text), ret));
+                continue;
+            } else if address == function.entry && function.frame > 0 {
+                (Item::Prologue, instruction.next())
+            } else if instruction.op == Op::AdjustBase
+                && function.frame > 0
+                && instruction.params[0].constant() == Some(-function.frame)
+                && analysis
+                    .instructions
+                    .get(&instruction.next())
+                    .is_some_and(|j| {
+                        j.is_indirect() && j.taken() == Some(true) && j.params[1] == relative(0)
+                    })
+            {
+                let next = analysis.instructions[&instruction.next()].next();
+                skip.insert(instruction.next());
+                (Item::Return, next)
+            } else if instruction.is_jump() && instruction.is_indirect() {
+                let cond = self.condition(&function, instruction, None);
+                let target = self.param(&function, instruction, 1);
+                let text = match cond {
+                    Some(cond) => format!("if {} {{ goto *{} }}", cond.render(), target),
+                    None => format!("goto *{}", target),
+                };
+                (Item::Statement(text), instruction.next())
+            } else if instruction.is_jump() {
+                let target = instruction.params[1].value as usize;
+                let cond = self.condition(&function, instruction, None);
+                if instruction.taken() == Some(false) {
+                    (Item::Statement(String::from("nop")), instruction.next())
+                } else {
+                    (Item::Jump(cond, target), instruction.next())
+                }
+            } else if let Some(jump) = self.folded_jump(instruction, &flags, &leaders) {
+                let cond = self.condition(&function, jump, Some(instruction));
+                skip.insert(jump.address);
+                let target = jump.params[1].value as usize;
+                (Item::Jump(cond, target), jump.next())
+            } else {
+                (
+                    Item::Statement(self.statement(&function, instruction)),
+                    instruction.next(),
+                )
+            };
+            items.insert(address, (item, next));
+        }
+        self.items = items;
+    }
+
+    fn emit(&mut self, depth: usize, text: String) {
+        self.lines.push(Line::Text(depth, text));
+    }
+
+    /* Where a jump goes, in terms of the innermost enclosing loop. */
+    fn jump(&mut self, target: usize) -> String {
+        match self.loops.last() {
+            Some(&(header, _)) if header == target => String::from("continue"),
+            Some(&(_, exit)) if exit == target => String::from("break"),
+            _ => {
+                self.gotos.insert(target);
+                format!("goto L{}", target)
+            }
+        }
+    }
+
+    /* The last jump back to this address from within [address, end). */
+    fn back_edge(&self, address: usize, end: usize) -> Option<usize> {
+        self.items
+            .range(address..end)
+            .rev()
+            .find(|(_, (item, _))| matches!(item, Item::Jump(_, t) if *t == address))
+            .map(|(&a, _)| a)
+    }
+
+    fn header(&self) -> Option<usize> {
+        self.loops.last().map(|&(header, _)| header)
+    }
+
+    /* Emits the statements in [start, end).  Falling off the end continues
+     * at `end` if `follows` is set, or at the top of the enclosing loop if
+     * not.
+     */
+    fn block(&mut self, start: usize, end: usize, follows: bool, depth: usize) {
+        let mut address = start;
+        while let Some((&a, (item, next))) = self.items.range(address..end).next() {
+            let (item, next) = (item.clone(), *next);
+            address = next;
+            self.lines.push(Line::Label(a));
+
+            if self.header() != Some(a) {
+                if let Some(last) = self.back_edge(a, end) {
+                    let exit = self.items[&last].1;
+                    self.emit(depth, String::from("loop {"));
+                    self.loops.push((a, exit));
+                    self.block(a, exit, false, depth + 1);
+                    self.loops.pop();
+                    self.emit(depth, String::from("}"));
+                    address = exit;
+                    continue;
+                }
+            }
+
+            match item {
+                Item::Prologue => {}
+                Item::Statement(text) => self.emit(depth, text),
+                Item::Return if self.function.returns => {
+                    self.emit(depth, String::from("return arg1"))
+                }
+                Item::Return => self.emit(depth, String::from("return")),
+                Item::Jump(_, target)
+                    if target > a && self.items.range(next..target).next().is_none() =>
+                { /* Only jumps over data. */ }
+                Item::Jump(cond, target) => {
+                    let inside = target > a && (target < end || (target == end && follows));
+                    let repeats = next == end && !follows && self.header() == Some(target);
+                    match cond {
+                        Some(cond) if inside => {
+                            address = self.branch(cond, next, target, end, follows, depth);
+                        }
+                        Some(cond) if repeats => {
+                            self.emit(depth, format!("if {} {{ break }}", cond.negate().render()));
+                        }
+                        Some(cond) => {
+                            let jump = self.jump(target);
+                            self.emit(depth, format!("if {} {{ {} }}", cond.render(), jump));
+                        }
+                        None if repeats || (inside && target == end) => {}
+                        None => {
+                            let jump = self.jump(target);
+                            self.emit(depth, jump);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /* An if, or an if/else if the `then` part ends by jumping over an `else`
+     * part.  Returns the address following it.
+     */
+    fn branch(
+        &mut self,
+        cond: Cond,
+        next: usize,
+        target: usize,
+        end: usize,
+        follows: bool,
+        depth: usize,
+    ) -> usize {
+        let skip =
+            self.items
+                .range(next..target)
+                .next_back()
+                .and_then(|(&a, (item, n))| match item {
+                    Item::Jump(None, e)
+                        if *n == target && *e > target && (*e < end || (*e == end && follows)) =>
+                    {
+                        Some((a, *e))
+                    }
+                    _ => None,
+                });
+        self.emit(depth, format!("if {} {{", cond.negate().render()));
+        match skip {
+            Some((jump, e)) => {
+                self.block(next, jump, true, depth + 1);
+                if self.items.range(target..e).next().is_some() {
+                    self.emit(depth, String::from("} else {"));
+                    self.block(target, e, true, depth + 1);
+                }
+                self.emit(depth, String::from("}"));
+                e
+            }
+            None => {
+                self.block(next, target, true, depth + 1);
+                self.emit(depth, String::from("}"));
+                target
+            }
+        }
+    }
+
+    pub fn decompile(&mut self) -> String {
+        self.find_items();
+        self.lines.clear();
+        self.gotos.clear();
+        let functions: Vec<Function> = self.functions.values().cloned().collect();
+        for function in &functions {
+            let params: Vec<String> = (1..=function.params).map(|i| format!("arg{}", i)).collect();
+            self.emit(
+                0,
+                format!("fn {}({}) {{", function.name(), params.join(", ")),
+            );
+            let locals = (function.frame - 1).max(0) as usize - function.params;
+            if locals > 0 {
+                let names: Vec<String> = (1..=locals).map(|i| format!("local{}", i)).collect();
+                self.emit(1, format!("var {}", names.join(", ")));
+            }
+            self.function = function.clone();
+            self.block(function.entry, function.end, true, 1);
+            self.emit(0, String::from("}"));
+            self.emit(0, String::new());
+        }
+
+        let mut out = String::new();
+        for &cell in &self.globals {
+            let value = self.analysis.program.get(cell).cloned().unwrap_or(0);
+            out += &format!("var g{} = {}\n", cell, value);
+        }
+        if !self.globals.is_empty() {
+            out += "\n";
+        }
+        for line in &self.lines {
+            match line {
+                Line::Label(a) if self.gotos.contains(a) => out += &format!("L{}:\n", a),
+                Line::Label(_) => {}
+                Line::Text(_, text) if text.is_empty() => out += "\n",
+                Line::Text(depth, text) => out += &format!("{}{}\n", "    ".repeat(*depth), text),
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+}
+
+pub fn decompile(program: &[i64]) -> String {
+    let analysis = Analysis::new(program);
+    Decompiler::new(&analysis).decompile()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workload::WORKLOADS;
+    use crate::{data_program, State, VM};
+
+    /* Prints 0 or 1 for the sign of its input, doubles it in a function,
+     * then counts down from there.
+     */
+    const PROGRAM: &[i64] = &[
+        109, 100, 3, 70, 1007, 70, 0, 71, 1006, 71, 16, 104, 0, 1105, 1, 18, 104, 1, 21001, 70, 0,
+        1, 21101, 29, 0, 0, 1105, 1, 50, 1201, 1, 0, 70, 4, 70, 1001, 70, -1, 70, 1007, 70, 1, 71,
+        1006, 71, 33, 99, 0, 0, 0, 109, 2, 21202, -1, 2, -1, 109, -2, 2105, 1, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0,
+    ];
+
+    /* The gotos and labels in some decompiled code, in order. */
+    fn gotos(code: &str) -> Vec<&str> {
+        code.lines()
+            .map(str::trim)
+            .filter(|line| line.contains("goto") || (line.starts_with('L') && line.ends_with(':')))
+            .collect()
+    }
+
+    #[test]
+    fn test_program() {
+        let mut vm = VM::new(&PROGRAM.to_vec());
+        vm.input.push_back(2);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.drain_output(), vec![1, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_decompile() {
+        assert_eq!(
+            decompile(PROGRAM),
+            "var g70 = 0
+
+fn main() {
+    rb += 100
+    g70 = input()
+    if g70 < 0 {
+        output(0)
+    } else {
+        output(1)
+    }
+    t1 = f50(g70)
+    g70 = t1
+    loop {
+        output(g70)
+        g70 = g70 - 1
+        if g70 < 1 { break }
+    }
+    halt
+}
+
+fn f50(arg1) {
+    arg1 = arg1 * 2
+    return arg1
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_day13() {
+        let program = data_program("day13-input");
+        let analysis = Analysis::new(&program);
+        let decompiler = Decompiler::new(&analysis);
+        let summary: Vec<(usize, i64, usize, bool)> = decompiler
+            .functions
+            .values()
+            .map(|f| (f.entry, f.frame, f.params, f.returns))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 0, 0, false),
+                (393, 3, 2, false),
+                (456, 8, 4, true),
+                (549, 4, 3, false),
+                (578, 3, 2, true),
+                (601, 3, 2, true),
+            ]
+        );
+
+        let code = decompile(&program);
+        assert!(code.contains("fn f578(arg1, arg2) {\n    g593 = arg2 * 35\n"));
+        assert!(code.contains("    arg1 = mem[g593]\n    return arg1\n"));
+        assert!(code.contains("t1 = f456(t1, 439, 399, 875)"));
+
+        /* Calls are recognised, so the only base adjustment left is the one
+         * setting up the stack.
+         */
+        assert!(code.contains("            t1 = f578(g382, g383)\n"));
+        assert!(code.contains("    f549(g388, g389, 4)\n"));
+        assert_eq!(code.matches("rb ").count(), 1);
+        assert!(code.contains("    rb += 2390\n"));
+
+        /* Drawing the screen is a loop over rows and columns. */
+        assert!(code.contains(
+            "    g383 = 0
+    loop {
+        g382 = 0
+        loop {
+            t1 = f578(g382, g383)
+            output(g382)
+            output(g383)
+            output(t1)
+            g382 = g382 + 1
+            if g382 >= 35 { break }
+        }
+        g383 = g383 + 1
+        if g383 >= 25 { break }
+    }
+"
+        ));
+
+        /* Globals keep their initial values, and frame slots beyond the
+         * parameters are locals.
+         */
+        assert!(code.contains("var g387 = 309\n"));
+        assert!(code.contains(
+            "fn f456(arg1, arg2, arg3, arg4) {
+    var local1, local2, local3
+    local1 = arg1 * arg2
+"
+        ));
+
+        /* Moving the paddle shares its tail between left and right, which
+         * is the only flow left as gotos.
+         */
+        assert_eq!(
+            gotos(&code),
+            ["if 0 < g384 { goto L108 }", "goto L119", "L108:", "L119:"]
+        );
+    }
+
+    #[test]
+    fn test_day9() {
+        let code = decompile(&data_program("input-day9"));
+        /* The recursive function the BOOST program finishes with. */
+        assert!(code.contains(
+            "fn f922(arg1) {
+    var local1
+    g63 = arg1 < 3
+    if g63 == 0 {
+        t1 = f922(arg1 - 1)
+        local1 = t1
+        t1 = f922(arg1 - 3)
+        arg1 = t1 + local1
+    } else {
+        arg1 = arg1
+    }
+    return arg1
+}
+"
+        ));
+        /* The self-test jumps through computed addresses, which can't be
+         * structured.
+         */
+        assert_eq!(
+            gotos(&code),
+            [
+                "if g63 != 0 { goto L65 }",
+                "if g63 != 0 { goto L904 }",
+                "if g63 != 0 { goto L58 }",
+                "L58:",
+                "L65:",
+                "goto *t8",
+                "goto L371",
+                "L371:",
+                "goto *t4",
+                "goto *t1",
+                "goto L673",
+                "L673:",
+                "goto *mem[rb - 1]",
+                "goto L691",
+                "L691:",
+                "L904:",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data() {
+        for name in WORKLOADS.iter().map(|w| w.name) {
+            let code = decompile(&data_program(name));
+            assert!(code.contains("fn main() {"), "{}", name);
+            let opened = code.matches('{').count();
+            assert_eq!(opened, code.matches('}').count(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::VM;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* A copy of a VM's memory.  Trailing zeros are dropped, and read back as
+ * zero, so snapshots of the same VM compare equal whatever its memory size.
+ * Saved snapshots are comma-separated, like programs, so they can also be
+ * loaded and run as one.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    cells: Vec<i64>,
+}
+
+impl Snapshot {
+    pub fn new(mut cells: Vec<i64>) -> Self {
+        while cells.last() == Some(&0) {
+            cells.pop();
+        }
+        Self { cells }
+    }
+
+    pub fn of(vm: &VM) -> Self {
+        Self::new(vm.memory().to_vec())
+    }
+
+    pub fn get(&self, address: usize) -> i64 {
+        self.cells.get(address).copied().unwrap_or(0)
+    }
+
+    /* One past the last non-zero cell. */
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /* Addresses whose value differs from previous. */
+    pub fn changed(&self, previous: &Snapshot) -> Vec<usize> {
+        (0..self.len().max(previous.len()))
+            .filter(|&a| self.get(a) != previous.get(a))
+            .collect()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, format!("{}\n", self))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.cells.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Snapshot {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let cells = s
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| {
+                x.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("bad cell {:?}", x))
+                })
+            })
+            .collect::<io::Result<Vec<i64>>>()?;
+        Ok(Self::new(cells))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /* Cells per row. */
+    pub width: usize,
+    /* List just the cells which changed, with their old and new values. */
+    pub changed_only: bool,
+    /* Highlight changes with ANSI reverse video rather than a '*'. */
+    pub colour: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            width: 8,
+            changed_only: false,
+            colour: false,
+        }
+    }
+}
+
+/* The character a cell would be if it held ASCII. */
+fn ascii(value: i64) -> char {
+    if (32..127).contains(&value) {
+        value as u8 as char
+    } else {
+        '.'
+    }
+}
+
+/* Formats memory as rows of values, each labelled with the address of its
+ * first cell and followed by the cells as ASCII.  Given the previous
+ * snapshot, cells which have changed since are highlighted.
+ */
+pub fn dump(snapshot: &Snapshot, previous: Option<&Snapshot>, options: &Options) -> String {
+    let end = snapshot.len().max(previous.map_or(0, Snapshot::len));
+    let changed = |a: usize| previous.is_some_and(|p| p.get(a) != snapshot.get(a));
+    let digits = |s: &Snapshot| (0..end).map(|a| s.get(a).to_string().len()).max();
+    let w = digits(snapshot).max(previous.and_then(digits)).unwrap_or(1);
+
+    let mut s = String::new();
+    if options.changed_only {
+        for a in (0..end).filter(|&a| changed(a)) {
+            let (old, new) = (previous.unwrap().get(a), snapshot.get(a));
+            s.push_str(&format!(
+                "{:>6}: {:>w$} -> {:>w$}  {}{}\n",
+                a,
+                old,
+                new,
+                ascii(old),
+                ascii(new),
+                w = w
+            ));
+        }
+        return s;
+    }
+
+    for row in (0..end).step_by(options.width.max(1)) {
+        let cells = row..(row + options.width).min(end);
+        s.push_str(&format!("{:>6}:", row));
+        for a in cells.clone() {
+            let value = snapshot.get(a);
+            s.push_str(&match (changed(a), options.colour) {
+                (true, true) => format!(" \x1b[7m{:>w$}\x1b[0m ", value, w = w),
+                (true, false) => format!(" {:>w$}*", value, w = w),
+                (false, _) => format!(" {:>w$} ", value, w = w),
+            });
+        }
+        /* Line up the ASCII column on a short last row. */
+        for _ in cells.len()..options.width {
+            s.push_str(&" ".repeat(w + 2));
+        }
+        s.push_str(" |");
+        s.extend(cells.map(|a| ascii(snapshot.get(a))));
+        s.push_str("|\n");
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let before = Snapshot::new(vec![72, 105, 1000, 0, 5, 0, 0]);
+        let after = Snapshot::new(vec![72, 73, 1000, 0, 5, 0, 33]);
+        assert_eq!(before.len(), 5);
+        assert_eq!(after.changed(&before), vec![1, 6]);
+
+        let options = Options {
+            width: 4,
+            ..Options::default()
+        };
+        assert_eq!(
+            dump(&before, None, &options),
+            "     0:   72   105  1000     0  |Hi..|\n     4:    5                    |.|\n"
+        );
+        assert_eq!(
+            dump(&after, Some(&before), &options),
+            "     0:   72    73* 1000     0  |HI..|\n     4:    5     0    33*       |..!|\n"
+        );
+
+        let changed_only = Options {
+            changed_only: true,
+            ..options
+        };
+        assert_eq!(
+            dump(&after, Some(&before), &changed_only),
+            "     1:  105 ->   73  iI\n     6:    0 ->   33  .!\n"
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let mut vm = VM::new(&vec![1101, 2, 3, 7, 99]);
+        let before = Snapshot::of(&vm);
+        vm.run();
+        let after = Snapshot::of(&vm);
+        assert_eq!(after.changed(&before), vec![7]);
+
+        let path = std::env::temp_dir().join(format!("dump-{}.txt", std::process::id()));
+        after.save(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1101,2,3,7,99,0,0,5\n");
+        assert_eq!(Snapshot::load(&path).unwrap(), after);
+        fs::remove_file(&path).unwrap();
+        assert!("1,x".parse::<Snapshot>().is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/* The integer types a Point can be made of. */
+pub trait Coordinate:
+    Copy
+    + Ord
+    + Hash
+    + Default
+    + fmt::Debug
+    + fmt::Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    const ONE: Self;
+
+    fn abs(self) -> Self;
+    /* None if it's negative, or too big. */
+    fn to_usize(self) -> Option<usize>;
+    fn from_usize(n: usize) -> Self;
+}
+
+macro_rules! coordinate {
+    ($($t:ty),*) => {
+        $(impl Coordinate for $t {
+            const ONE: Self = 1;
+
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+
+            fn to_usize(self) -> Option<usize> {
+                use std::convert::TryFrom;
+                usize::try_from(self).ok()
+            }
+
+            fn from_usize(n: usize) -> Self {
+                n as $t
+            }
+        })*
+    };
+}
+
+coordinate!(i32, i64, isize);
+
+/* Positions on a screen: x grows to the right and y grows downwards. */
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Point<T = i64> {
+    pub x: T,
+    pub y: T,
+}
+
+impl<T: Coordinate> Point<T> {
+    pub fn new(x: T, y: T) -> Self {
+        Self { x, y }
+    }
+
+    /* Distance from the origin, moving only along the axes. */
+    pub fn manhattan(self) -> T {
+        self.x.abs() + self.y.abs()
+    }
+
+    /* The four points next to this one, in the order of Direction::ALL. */
+    pub fn neighbours(self) -> impl Iterator<Item = Self> {
+        Direction::ALL.iter().map(move |d| d.advance(self))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl<T: Coordinate> Add for Point<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl<T: Coordinate> Sub for Point<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl<T: Coordinate> Mul<T> for Point<T> {
+    type Output = Self;
+
+    fn mul(self, n: T) -> Self {
+        Self::new(self.x * n, self.y * n)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    N,
+    E,
+    S,
+    W,
+}
+
+impl Direction {
+    /* Clockwise, from the top. */
+    pub const ALL: [Self; 4] = [Self::N, Self::E, Self::S, Self::W];
+
+    /* A quarter turn anticlockwise. */
+    pub fn left(self) -> Self {
+        match self {
+            Self::N => Self::W,
+            Self::E => Self::N,
+            Self::S => Self::E,
+            Self::W => Self::S,
+        }
+    }
+
+    /* A quarter turn clockwise. */
+    pub fn right(self) -> Self {
+        match self {
+            Self::N => Self::E,
+            Self::E => Self::S,
+            Self::S => Self::W,
+            Self::W => Self::N,
+        }
+    }
+
+    pub fn reverse(self) -> Self {
+        self.left().left()
+    }
+
+    /* One step this way. */
+    pub fn delta<T: Coordinate>(self) -> Point<T> {
+        let (zero, one) = (T::default(), T::ONE);
+        match self {
+            Self::N => Point::new(zero, -one),
+            Self::E => Point::new(one, zero),
+            Self::S => Point::new(zero, one),
+            Self::W => Point::new(-one, zero),
+        }
+    }
+
+    pub fn advance<T: Coordinate>(self, p: Point<T>) -> Point<T> {
+        p + self.delta()
+    }
+}
+
+/* The smallest rectangle holding some points, edges included. */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bounds<T = i64> {
+    pub min: Point<T>,
+    pub max: Point<T>,
+}
+
+impl<T: Coordinate> Bounds<T> {
+    /* None if there are no points. */
+    pub fn of<I: IntoIterator<Item = Point<T>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut bounds = Self {
+            min: first,
+            max: first,
+        };
+        for p in points {
+            bounds.include(p);
+        }
+        Some(bounds)
+    }
+
+    /* Grows to take in p. */
+    pub fn include(&mut self, p: Point<T>) {
+        self.min = Point::new(self.min.x.min(p.x), self.min.y.min(p.y));
+        self.max = Point::new(self.max.x.max(p.x), self.max.y.max(p.y));
+    }
+
+    pub fn contains(&self, p: Point<T>) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x && self.min.y <= p.y && p.y <= self.max.y
+    }
+
+    pub fn width(&self) -> T {
+        self.max.x - self.min.x + T::ONE
+    }
+
+    pub fn height(&self) -> T {
+        self.max.y - self.min.y + T::ONE
+    }
+
+    /* Row by row, top to bottom. */
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = Point<T>>> {
+        let (min, max) = (self.min, self.max);
+        span(min.y, max.y).map(move |y| span(min.x, max.x).map(move |x| Point::new(x, y)))
+    }
+}
+
+fn span<T: Coordinate>(from: T, to: T) -> impl Iterator<Item = T> {
+    std::iter::successors(Some(from), move |&n| Some(n + T::ONE)).take_while(move |&n| n <= to)
+}
+
+/* Cells at points on a plane.  Anywhere not set is None. */
+pub trait Grid<T: Coordinate = i64> {
+    type Cell;
+
+    fn get(&self, p: Point<T>) -> Option<&Self::Cell>;
+    fn set(&mut self, p: Point<T>, cell: Self::Cell);
+    /* Around every cell that's set; None if there aren't any. */
+    fn bounds(&self) -> Option<Bounds<T>>;
+
+    /* Draws the cells within the bounds, a line per row, top to bottom. */
+    fn render<F>(&self, mut f: F) -> String
+    where
+        F: FnMut(Point<T>, Option<&Self::Cell>) -> char,
+    {
+        let mut s = String::new();
+        if let Some(bounds) = self.bounds() {
+            for row in bounds.rows() {
+                s.extend(row.map(|p| f(p, self.get(p))));
+                s.push('\n');
+            }
+        }
+        s
+    }
+}
+
+/* For cells scattered about, or found bit by bit. */
+pub type SparseGrid<C, T = i64> = BTreeMap<Point<T>, C>;
+
+impl<T: Coordinate, C> Grid<T> for SparseGrid<C, T> {
+    type Cell = C;
+
+    fn get(&self, p: Point<T>) -> Option<&C> {
+        BTreeMap::get(self, &p)
+    }
+
+    fn set(&mut self, p: Point<T>, cell: C) {
+        self.insert(p, cell);
+    }
+
+    fn bounds(&self) -> Option<Bounds<T>> {
+        Bounds::of(self.keys().copied())
+    }
+}
+
+/* A rectangle of cells with its top left at the origin. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseGrid<C, T = i64> {
+    width: usize,
+    height: usize,
+    cells: Vec<C>,
+    /* What points are made of, to index it with. */
+    coordinate: PhantomData<T>,
+}
+
+impl<C: Clone, T> DenseGrid<C, T> {
+    pub fn new(width: usize, height: usize, fill: C) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![fill; width * height],
+            coordinate: PhantomData,
+        }
+    }
+
+    /* Rows must all be the same length. */
+    pub fn from_rows(rows: Vec<Vec<C>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(rows.iter().all(|r| r.len() == width), "ragged rows");
+        Self {
+            width,
+            height,
+            cells: rows.into_iter().flatten().collect(),
+            coordinate: PhantomData,
+        }
+    }
+
+    /* Grows or shrinks to the new size, keeping the cells still inside it. */
+    pub fn resize(&mut self, width: usize, height: usize, fill: C) {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(if x < self.width && y < self.height {
+                    self.cells[y * self.width + x].clone()
+                } else {
+                    fill.clone()
+                });
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+    }
+}
+
+impl<C, T: Coordinate> DenseGrid<C, T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[C]> {
+        /* chunks() won't take 0, and a grid with no columns has no cells. */
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &C> {
+        self.cells.iter()
+    }
+
+    fn index(&self, p: Point<T>) -> Option<usize> {
+        let (x, y) = (p.x.to_usize()?, p.y.to_usize()?);
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Coordinate, C> Grid<T> for DenseGrid<C, T> {
+    type Cell = C;
+
+    fn get(&self, p: Point<T>) -> Option<&C> {
+        self.index(p).map(|i| &self.cells[i])
+    }
+
+    /* Panics if p is outside the grid. */
+    fn set(&mut self, p: Point<T>, cell: C) {
+        let i = self
+            .index(p)
+            .unwrap_or_else(|| panic!("{} is off the grid", p));
+        self.cells[i] = cell;
+    }
+
+    fn bounds(&self) -> Option<Bounds<T>> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        Some(Bounds {
+            min: Point::default(),
+            max: Point::new(
+                T::from_usize(self.width - 1),
+                T::from_usize(self.height - 1),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directions() {
+        let mut d = Direction::N;
+        for expected in &[Direction::E, Direction::S, Direction::W, Direction::N] {
+            d = d.right();
+            assert_eq!(d, *expected);
+        }
+        assert_eq!(Direction::N.left(), Direction::W);
+        assert_eq!(Direction::E.reverse(), Direction::W);
+        let p: Point<i32> = Point::new(3, 4);
+        assert_eq!(Direction::N.advance(p), Point::new(3, 3));
+        assert_eq!(Direction::W.advance(p), Point::new(2, 4));
+        assert_eq!(p.neighbours().collect::<Vec<_>>()[1], Point::new(4, 4));
+        assert_eq!((p - Point::new(5, 5)).manhattan(), 3);
+        assert_eq!(Direction::S.delta::<i64>() * 3, Point::new(0, 3));
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(Bounds::<i64>::of(vec![]), None);
+        let bounds = Bounds::of(vec![Point::new(2, -1), Point::new(-3, 4)]).unwrap();
+        assert_eq!(bounds.min, Point::new(-3, -1));
+        assert_eq!((bounds.width(), bounds.height()), (6, 6));
+        assert!(bounds.contains(Point::new(0, 0)));
+        assert!(!bounds.contains(Point::new(3, 0)));
+        let rows: Vec<Vec<_>> = Bounds::of(vec![Point::new(0, 0), Point::new(1, 1)])
+            .unwrap()
+            .rows()
+            .map(Iterator::collect)
+            .collect();
+        assert_eq!(rows[1], vec![Point::new(0, 1), Point::new(1, 1)]);
+    }
+
+    #[test]
+    fn test_grids() {
+        let mut sparse = SparseGrid::new();
+        sparse.set(Point::new(-1, 0), '#');
+        sparse.set(Point::new(1, 1), '#');
+        let draw = |_, c: Option<&char>| *c.unwrap_or(&'.');
+        assert_eq!(sparse.render(draw), "#..\n..#\n");
+
+        let mut dense: DenseGrid<char> = DenseGrid::new(3, 2, '.');
+        dense.set(Point::new(2, 0), '#');
+        assert_eq!(dense.get(Point::new(2, 0)), Some(&'#'));
+        assert_eq!(dense.get(Point::new(-1, 0)), None);
+        assert_eq!(dense.get(Point::new(3, 0)), None);
+        dense.resize(4, 3, ' ');
+        assert_eq!(dense.render(draw), "..# \n... \n    \n");
+        let rows: DenseGrid<i32> = DenseGrid::from_rows(vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(rows.rows().collect::<Vec<_>>(), vec![&[1, 2], &[3, 4]]);
+        assert_eq!(DenseGrid::<u8>::new(0, 0, 0).bounds(), None);
+    }
+}
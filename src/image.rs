@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+pub type Rgb = [u8; 3];
+
+/* A picture whose pixels are indices into a palette. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, index: u8) {
+        self.pixels[y * self.width + x] = index;
+    }
+
+    /* Each pixel becomes a scale × scale square. */
+    pub fn scaled(&self, scale: usize) -> Self {
+        let mut frame = Self::new(self.width * scale, self.height * scale);
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                frame.set(x, y, self.get(x / scale, y / scale));
+            }
+        }
+        frame
+    }
+}
+
+/* Parses a colour written as six hex digits, as in "ff8000". */
+pub fn parse_rgb(s: &str) -> Option<Rgb> {
+    let s = s.trim_start_matches('#');
+    if s.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(s.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/* Binary PPM (P6): a header, then three bytes per pixel. */
+pub fn write_ppm<W: Write>(mut out: W, frame: &Frame, palette: &[Rgb]) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", frame.width, frame.height)?;
+    let bytes: Vec<u8> = frame
+        .pixels
+        .iter()
+        .flat_map(|&i| palette[i as usize].iter().copied())
+        .collect();
+    out.write_all(&bytes)
+}
+
+/* Binary PGM (P5): a header, then a byte per pixel, its grey level. */
+pub fn write_pgm<W: Write>(mut out: W, frame: &Frame, greys: &[u8]) -> io::Result<()> {
+    write!(out, "P5\n{} {}\n255\n", frame.width, frame.height)?;
+    let bytes: Vec<u8> = frame.pixels.iter().map(|&i| greys[i as usize]).collect();
+    out.write_all(&bytes)
+}
+
+/* Plain PBM (P1): pixels which aren't 0 are black. */
+pub fn write_pbm<W: Write>(mut out: W, frame: &Frame) -> io::Result<()> {
+    writeln!(out, "P1\n{} {}", frame.width, frame.height)?;
+    for row in frame.pixels.chunks(frame.width.max(1)) {
+        let bits: Vec<&str> = row
+            .iter()
+            .map(|&i| if i == 0 { "0" } else { "1" })
+            .collect();
+        writeln!(out, "{}", bits.join(" "))?;
+    }
+    Ok(())
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/* A zlib stream of stored (uncompressed) deflate blocks. */
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+        out.push(last);
+        out.extend(&len.to_le_bytes());
+        out.extend(&(!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(&adler32(data).to_be_bytes());
+    out
+}
+
+fn png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut checked = kind.to_vec();
+    checked.extend(data);
+    out.write_all(&crc32(&checked).to_be_bytes())
+}
+
+/* An 8-bit paletted PNG.  The pixels aren't compressed, which is fine for
+ * the little pictures drawn here.
+ */
+pub fn write_png<W: Write>(mut out: W, frame: &Frame, palette: &[Rgb]) -> io::Result<()> {
+    assert!(palette.len() <= 256, "PNGs have at most 256 colours");
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = Vec::new();
+    header.extend(&(frame.width as u32).to_be_bytes());
+    header.extend(&(frame.height as u32).to_be_bytes());
+    header.extend(&[8, 3, 0, 0, 0]);
+    png_chunk(&mut out, b"IHDR", &header)?;
+    png_chunk(&mut out, b"PLTE", &palette.concat())?;
+    /* Each row starts with its filter type, 0 for none. */
+    let mut raw = Vec::with_capacity((frame.width + 1) * frame.height);
+    for row in frame.pixels.chunks(frame.width.max(1)) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&raw))?;
+    png_chunk(&mut out, b"IEND", &[])
+}
+
+/* Packs variable-width codes into bytes, least significant bit first. */
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+const MAX_CODE: u16 = 4096;
+
+/* GIF's variant of LZW: codes start one bit wider than the pixels, grow as
+ * the table does, up to 12 bits, and the table starts over when full.
+ */
+fn lzw(pixels: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut out = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size + 1;
+    out.write(clear, width);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(&p) => p as u16,
+        None => {
+            out.write(end, width);
+            return out.finish();
+        }
+    };
+    for &p in pixels {
+        if let Some(&code) = table.get(&(prefix, p)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, width);
+        if next < MAX_CODE {
+            table.insert((prefix, p), next);
+            if next >= 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        } else {
+            out.write(clear, width);
+            table.clear();
+            next = end + 1;
+            width = min_code_size + 1;
+        }
+        prefix = p as u16;
+    }
+    out.write(prefix, width);
+    out.write(end, width);
+    out.finish()
+}
+
+/* An animated GIF, which loops forever. */
+pub struct Gif<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    /* In hundredths of a second. */
+    delay: u16,
+    min_code_size: u32,
+}
+
+impl<W: Write> Gif<W> {
+    pub fn new(
+        mut out: W,
+        width: usize,
+        height: usize,
+        palette: &[Rgb],
+        delay: u16,
+    ) -> io::Result<Self> {
+        assert!(palette.len() <= 256, "GIFs have at most 256 colours");
+        /* The colour table holds a power of two entries, at least 4. */
+        let mut bits = 2;
+        while 1 << bits < palette.len() {
+            bits += 1;
+        }
+        let (width, height) = (width as u16, height as u16);
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&[0xf0 | (bits - 1) as u8, 0, 0])?;
+        for i in 0..1 << bits {
+            out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            delay,
+            min_code_size: bits,
+        })
+    }
+
+    pub fn add(&mut self, frame: &Frame) -> io::Result<()> {
+        assert_eq!(
+            (frame.width, frame.height),
+            (self.width as usize, self.height as usize)
+        );
+        let delay = self.delay.to_le_bytes();
+        self.out
+            .write_all(&[0x21, 0xf9, 4, 0, delay[0], delay[1], 0, 0])?;
+        self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0, self.min_code_size as u8])?;
+        for block in lzw(&frame.pixels, self.min_code_size).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Undoes lzw(), the way a GIF decoder does. */
+    fn unlzw(bytes: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            table.clear();
+            table.extend((0..clear).map(|i| vec![i as u8]));
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+        let mut width = min_code_size + 1;
+        let (mut buffer, mut bits, mut i) = (0u32, 0, 0);
+        let mut previous: Option<Vec<u8>> = None;
+        let mut pixels = Vec::new();
+        loop {
+            while bits < width {
+                buffer |= (bytes[i] as u32) << bits;
+                i += 1;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << width) - 1)) as usize;
+            buffer >>= width;
+            bits -= width;
+            if code == clear {
+                reset(&mut table);
+                width = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return pixels;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(p)) => {
+                    let mut entry = p.clone();
+                    entry.push(p[0]);
+                    entry
+                }
+                (None, None) => panic!("bad code {}", code),
+            };
+            if let Some(mut p) = previous {
+                if table.len() < 4096 {
+                    p.push(entry[0]);
+                    table.push(p);
+                    if table.len() == 1 << width && width < 12 {
+                        width += 1;
+                    }
+                }
+            }
+            pixels.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        let mut seed = 1u32;
+        let noise: Vec<u8> = (0..20000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8 % 5
+            })
+            .collect();
+        for pixels in [vec![], vec![3], vec![1; 5000], noise] {
+            assert_eq!(unlzw(&lzw(&pixels, 3), 3), pixels);
+        }
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut frame = Frame::new(2, 1);
+        frame.set(1, 0, 1);
+        let palette = [[0, 0, 0], [255, 255, 255]];
+        let mut gif = Gif::new(Vec::new(), 4, 2, &palette, 5).unwrap();
+        gif.add(&frame.scaled(2)).unwrap();
+        let bytes = gif.finish().unwrap();
+        assert_eq!(&bytes[..13], b"GIF89a\x04\x00\x02\x00\xf1\x00\x00");
+        assert_eq!(&bytes[13..19], &[0, 0, 0, 255, 255, 255]);
+        assert_eq!(bytes.last(), Some(&0x3b));
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut frame = Frame::new(2, 1);
+        frame.set(0, 0, 1);
+        let mut out = Vec::new();
+        write_ppm(&mut out, &frame, &[[1, 2, 3], [4, 5, 6]]).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\x04\x05\x06\x01\x02\x03");
+        assert_eq!(parse_rgb("#ff8000"), Some([255, 128, 0]));
+        assert_eq!(parse_rgb("ff80"), None);
+    }
+
+    #[test]
+    fn test_pgm() {
+        let mut frame = Frame::new(3, 1);
+        frame.set(1, 0, 2);
+        let mut out = Vec::new();
+        write_pgm(&mut out, &frame, &[0, 255, 128]).unwrap();
+        assert_eq!(out, b"P5\n3 1\n255\n\x00\x80\x00");
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut frame = Frame::new(3, 2);
+        frame.set(1, 0, 1);
+        frame.set(2, 1, 4);
+        let mut out = Vec::new();
+        write_pbm(&mut out, &frame).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "P1\n3 2\n0 1 0\n0 0 1\n");
+    }
+
+    #[test]
+    fn test_png() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let mut frame = Frame::new(2, 2);
+        frame.set(1, 1, 1);
+        let mut out = Vec::new();
+        write_png(&mut out, &frame, &[[0, 0, 0], [255, 255, 255]]).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[16..29], &[0, 0, 0, 2, 0, 0, 0, 2, 8, 3, 0, 0, 0]);
+        assert_eq!(&out[out.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+        /* The rows, filter bytes and all, stored as they are. */
+        let idat = out.windows(4).position(|w| w == b"IDAT").unwrap() + 4;
+        assert_eq!(&out[idat..idat + 7], &[0x78, 0x01, 1, 6, 0, 0xf9, 0xff]);
+        assert_eq!(&out[idat + 7..idat + 13], &[0, 0, 0, 0, 0, 1]);
+
+        let big = vec![7; 0x1_0000 + 10];
+        let stored = zlib_stored(&big);
+        assert_eq!(stored.len(), 2 + 5 + 0xffff + 5 + 11 + 4);
+        assert_eq!(stored[2], 0);
+        assert_eq!(stored[2 + 5 + 0xffff], 1);
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+    }
+}
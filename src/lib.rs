@@ -1,17 +1,62 @@
 use std::collections::VecDeque;
+use std::fs;
 use std::io::{self, BufRead};
+use std::path::Path;
+
+pub mod analysis;
+pub mod cheat;
+pub mod compiled;
+pub mod cooperative;
+pub mod decompile;
+pub mod dump;
+pub mod grid;
+pub mod image;
+pub mod memory;
+pub mod network;
+pub mod observe;
+pub mod ocr;
+pub mod opcodes;
+pub mod optimize;
+pub mod outputs;
+pub mod replay;
+pub mod search;
+pub mod strict;
+pub mod transpile;
+pub mod workload;
+
+use memory::PagedMemory;
+use observe::{Observer, Observers};
+use opcodes::OpcodeTable;
+use outputs::Outputs;
+use replay::{Event, Recording};
+use std::sync::Arc;
+use strict::{Checker, Validation, Violation};
 
 pub type Memory = Vec<i64>;
 pub type IO = VecDeque<i64>;
 
+#[derive(Clone)]
 pub struct VM {
-    memory: Memory,
+    memory: PagedMemory,
     ip: usize,
     relative_base: i64,
+    steps: u64,
+    recording: Option<Recording>,
+    opcodes: Arc<OpcodeTable>,
+    /* Length of the program the VM was loaded with. */
+    image: usize,
+    /* Only in strict mode. */
+    checker: Option<Checker>,
+    observers: Observers,
+    /* Why the instruction at ip stopped, if it did: it runs again next
+     * time, but observers have already been told about it.
+     */
+    stopped: Option<State>,
     pub input: IO,
     pub output: IO,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum State {
     Halted,
     NeedInput,
@@ -19,104 +64,207 @@ pub enum State {
 
 impl VM {
     pub fn new(program: &Memory) -> Self {
-        let mut memory = program.clone();
-        memory.resize(program.len() * 10, 0);
+        Self::with_table(program, OpcodeTable::shared())
+    }
+
+    /* A VM running a dialect of Intcode with extra (or fewer) instructions. */
+    pub fn with_opcodes(program: &Memory, opcodes: OpcodeTable) -> Self {
+        Self::with_table(program, Arc::new(opcodes))
+    }
+
+    fn with_table(program: &Memory, opcodes: Arc<OpcodeTable>) -> Self {
         Self {
-            memory,
+            memory: PagedMemory::new(program, program.len() * 10),
             ip: 0,
             relative_base: 0,
+            steps: 0,
+            recording: None,
+            opcodes,
+            image: program.len(),
+            checker: None,
+            observers: Observers::default(),
+            stopped: None,
             input: IO::new(),
             output: IO::new(),
         }
     }
 
-    fn arg(&mut self, i: u32) -> &mut i64 {
-        let instruction = self.memory[self.ip];
+    fn address(&self, i: u32) -> usize {
+        let instruction = self.memory.get(self.ip);
         let mode = (instruction / (10 * (10_i64.pow(i)))) % 10;
         let j = self.ip + i as usize;
         match mode {
-            0 => {
-                let val = self.memory[j];
-                &mut self.memory[val as usize]
-            }
-            1 => &mut self.memory[j],
-            2 => {
-                let address: i64 = self.memory[j] + self.relative_base;
-                &mut self.memory[address as usize]
-            }
+            0 => self.memory.get(j) as usize,
+            1 => j,
+            2 => (self.memory.get(j) + self.relative_base) as usize,
             _ => panic!("{}", mode),
         }
     }
 
-    fn int3<F>(&mut self, f: F)
-    where
-        F: Fn(i64, i64) -> i64,
-    {
-        let x = *self.arg(1);
-        let y = *self.arg(2);
-        *self.arg(3) = f(x, y);
-        self.ip += 4
+    fn arg(&self, i: u32) -> i64 {
+        self.memory.get(self.address(i))
     }
 
-    fn jump_if<F>(&mut self, f: F)
-    where
-        F: Fn(i64) -> bool,
-    {
-        if f(*self.arg(1)) {
-            self.ip = *self.arg(2) as usize
-        } else {
-            self.ip += 3;
+    fn set_arg(&mut self, i: u32, value: i64) {
+        self.poke(self.address(i), value);
+    }
+
+    /* Takes the VM's own opcode table, borrowed once by the caller rather
+     * than cloned for every instruction.
+     */
+    fn step(&mut self, opcodes: &OpcodeTable) -> Option<State> {
+        match opcodes::execute(self, opcodes) {
+            Ok(state) => state,
+            Err(violation) => panic!("{}", violation),
         }
     }
 
-    fn step(&mut self) -> Option<State> {
-        let instruction = self.memory[self.ip];
-        let masked = instruction % 100;
-        match masked {
-            1 => self.int3(|a, b| a + b),
-            2 => self.int3(|a, b| a * b),
-            3 => {
-                let val_ = self.input.pop_front();
-                match val_ {
-                    Some(val) => {
-                        *self.arg(1) = val;
-                        self.ip += 2;
-                    }
-                    None => {
-                        return Some(State::NeedInput);
-                    }
-                }
-            }
-            4 => {
-                let val = *self.arg(1);
-                self.output.push_back(val);
-                self.ip += 2
-            }
-            5 => self.jump_if(|x| x != 0),
-            6 => self.jump_if(|x| x == 0),
-            7 => self.int3(|a, b| (a < b) as i64),
-            8 => self.int3(|a, b| (a == b) as i64),
-            9 => {
-                self.relative_base += *self.arg(1);
-                self.ip += 2;
+    /* Set before running: in strict mode, run() panics at the first
+     * violation and try_run() returns it.
+     */
+    pub fn set_validation(&mut self, validation: Validation) {
+        self.checker = match validation {
+            Validation::Lenient => None,
+            Validation::Strict => Some(Checker::default()),
+        };
+    }
+
+    /* Installs an observer, which is told about everything the VM does from
+     * now on.  Any number may be installed; they're called in the order they
+     * were added.
+     */
+    pub fn observe<T: Observer>(&mut self, observer: T) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /* The first installed observer of type T. */
+    pub fn observer<T: Observer>(&self) -> Option<&T> {
+        self.observers.get()
+    }
+
+    pub fn take_observer<T: Observer>(&mut self) -> Option<T> {
+        self.observers.take()
+    }
+
+    fn log(&mut self, event: Event) {
+        if let Some(recording) = &mut self.recording {
+            recording.events.push(event);
+        }
+    }
+
+    /* Runs at most n instructions, stopping early if the VM halts or needs
+     * input.
+     */
+    pub fn run_steps(&mut self, n: u64) -> Option<State> {
+        let opcodes = self.opcodes.clone();
+        for _ in 0..n {
+            if let Some(x) = self.step(&opcodes) {
+                return Some(x);
             }
-            99 => return Some(State::Halted),
-            x => panic!("the discotheque: ip {}: {}", self.ip, x),
         }
         None
     }
 
     pub fn run(&mut self) -> State {
+        let opcodes = self.opcodes.clone();
         loop {
-            if let Some(x) = self.step() {
+            if let Some(x) = self.step(&opcodes) {
                 return x;
             }
         }
     }
 
+    pub fn try_run(&mut self) -> Result<State, Violation> {
+        let opcodes = self.opcodes.clone();
+        loop {
+            if let Some(x) = opcodes::execute(self, &opcodes)? {
+                return Ok(x);
+            }
+        }
+    }
+
+    /* Runs lazily, yielding outputs as they appear and asking input for a
+     * value whenever the program needs one.
+     */
+    pub fn outputs<F: FnMut(&VM) -> Option<i64>>(&mut self, input: F) -> Outputs<'_, F> {
+        Outputs::new(self, input)
+    }
+
     pub fn drain_output(&mut self) -> IO {
         self.output.split_off(0)
     }
+
+    /* Cheap: memory pages are shared with the original until either writes to
+     * them.
+     */
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn opcodes(&self) -> &OpcodeTable {
+        &self.opcodes
+    }
+
+    /* Overwrites a cell, as if the program had written it. */
+    pub fn poke(&mut self, address: usize, value: i64) {
+        let old = if self.observers.is_empty() {
+            0
+        } else {
+            self.memory.get(address)
+        };
+        self.memory.set(address, value);
+        if let Some(checker) = &mut self.checker {
+            checker.wrote(self.image, address);
+        }
+        if !self.observers.is_empty() {
+            observe::notify(self, |o, vm| o.on_memory_write(vm, address, old, value));
+        }
+    }
+
+    pub fn memory(&self) -> &PagedMemory {
+        &self.memory
+    }
+
+    /* Number of instructions executed so far. */
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /* Start logging every value consumed by opcode 3 and produced by opcode 4. */
+    pub fn record(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+}
+
+/* Something which runs Intcode on a VM: the VM itself, or code generated by
+ * transpile, which keeps its state in one.
+ */
+pub trait Machine {
+    fn run(&mut self) -> State;
+    fn vm(&self) -> &VM;
+    fn vm_mut(&mut self) -> &mut VM;
+}
+
+impl Machine for VM {
+    fn run(&mut self) -> State {
+        VM::run(self)
+    }
+
+    fn vm(&self) -> &VM {
+        self
+    }
+
+    fn vm_mut(&mut self) -> &mut VM {
+        self
+    }
 }
 
 pub fn program_from_stdin() -> Memory {
@@ -127,7 +275,49 @@ pub fn program_from_stdin() -> Memory {
         .map(|l| l.unwrap())
         .collect::<Vec<String>>()
         .join("");
-    line.split(",").map(|s| s.parse().unwrap()).collect()
+    program_from_str(&line)
+}
+
+pub fn program_from_str(source: &str) -> Memory {
+    source
+        .trim()
+        .split(',')
+        .map(|s| s.trim().parse().unwrap())
+        .collect()
+}
+
+/* A file from the data directory, for tests. */
+pub fn data_file(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("data")
+        .join(name);
+    fs::read_to_string(path).expect(name)
+}
+
+pub fn data_program(name: &str) -> Memory {
+    program_from_str(&data_file(name))
+}
+
+pub fn gcd(x: i64, y: i64) -> i64 {
+    assert!(x >= 0);
+    assert!(y >= 0);
+    let (mut a, mut b) = if x > y { (x, y) } else { (y, x) };
+
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+
+    a
+}
+
+pub fn lcm(x: i64, y: i64) -> i64 {
+    assert!(x >= 0);
+    assert!(y >= 0);
+
+    let g = gcd(x, y);
+    (x / g) * y
 }
 
 #[cfg(test)]
@@ -140,16 +330,17 @@ mod tests {
         let mut vm = VM::new(&program);
         vm.run();
 
-        vm.memory.resize(program.len(), 0);
-        assert_eq!(vm.memory, vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+        let mut memory = vm.memory.to_vec();
+        memory.resize(program.len(), 0);
+        assert_eq!(memory, vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
     }
 
     fn run_with_input(program: &Memory, mut input: IO) -> (Memory, IO) {
-        let mut vm = VM::new(&program);
+        let mut vm = VM::new(program);
         vm.input.append(&mut input);
         vm.run();
 
-        (vm.memory, vm.output)
+        (vm.memory.to_vec(), vm.output)
     }
 
     #[test]
@@ -177,31 +368,50 @@ mod tests {
     }
 
     #[test]
-    fn test_day9_large() {
-        let program = vec![104, 1125899906842624, 99];
-        let (_, output) = run_with_input(&program, IO::new());
-        assert_eq!(output, vec![program[1]]);
-    }
-}
+    fn test_fork() {
+        /* Reads a value into 11, then repeatedly outputs and doubles it. */
+        let program = vec![3, 11, 4, 11, 1002, 11, 2, 11, 1105, 1, 2];
+        let mut a = VM::new(&program);
+        a.run();
 
-pub fn gcd(x: i64, y: i64) -> i64 {
-    assert!(x >= 0);
-    assert!(y >= 0);
-    let (mut a, mut b) = if x > y { (x, y) } else { (y, x) };
+        let mut b = a.fork();
+        a.input.push_back(1);
+        b.input.push_back(5);
+        a.run_steps(5);
+        b.run_steps(9);
 
-    while b != 0 {
-        let r = a % b;
-        a = b;
-        b = r;
+        assert_eq!(a.drain_output(), vec![1, 2]);
+        assert_eq!(b.drain_output(), vec![5, 10, 20]);
+        assert_eq!(a.memory().get(11), 2);
+        assert_eq!(b.memory().get(11), 40);
     }
 
-    a
-}
+    #[test]
+    fn test_fork_memory() {
+        let mut program = vec![7; memory::PAGE_SIZE * 8];
+        /* Write 1 into the last cell of pages 4, 5 and 6, then halt. */
+        for (i, page) in [4, 5, 6].iter().enumerate() {
+            let address = (page + 1) * memory::PAGE_SIZE - 1;
+            program[i * 4..i * 4 + 4].copy_from_slice(&[1101, 0, 1, address as i64]);
+        }
+        program[12] = 99;
+        let a = VM::new(&program);
+        assert_eq!(a.memory().private_pages(), 8);
 
-pub fn lcm(x: i64, y: i64) -> i64 {
-    assert!(x >= 0);
-    assert!(y >= 0);
+        let mut b = a.fork();
+        assert_eq!(a.memory().private_pages(), 0);
+        assert_eq!(b.memory().private_pages(), 0);
 
-    let g = gcd(x, y);
-    (x / g) * y
+        b.run();
+        assert_eq!(b.memory().private_pages(), 3);
+        assert_eq!(a.memory().get(5 * memory::PAGE_SIZE - 1), 7);
+        assert_eq!(b.memory().get(5 * memory::PAGE_SIZE - 1), 1);
+    }
+
+    #[test]
+    fn test_day9_large() {
+        let program = vec![104, 1125899906842624, 99];
+        let (_, output) = run_with_input(&program, IO::new());
+        assert_eq!(output, vec![program[1]]);
+    }
 }
@@ -0,0 +1,122 @@
+use crate::Memory;
+use std::sync::Arc;
+
+pub const PAGE_SIZE: usize = 256;
+
+type Page = [i64; PAGE_SIZE];
+
+/* VM memory, split into pages which are shared between forks of a VM until
+ * one of them writes to it.  Pages that have never been written are not
+ * allocated at all and read as zero.
+ */
+#[derive(Clone)]
+pub struct PagedMemory {
+    pages: Vec<Option<Arc<Page>>>,
+    len: usize,
+}
+
+impl PagedMemory {
+    pub fn new(program: &[i64], len: usize) -> Self {
+        let mut memory = Self {
+            pages: vec![None; len.div_ceil(PAGE_SIZE)],
+            len,
+        };
+        for (address, &value) in program.iter().enumerate() {
+            if value != 0 {
+                memory.set(address, value);
+            }
+        }
+        memory
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn check(&self, address: usize) {
+        if address >= self.len {
+            panic!(
+                "address {} out of range (memory size {})",
+                address, self.len
+            );
+        }
+    }
+
+    pub fn get(&self, address: usize) -> i64 {
+        self.check(address);
+        match &self.pages[address / PAGE_SIZE] {
+            Some(page) => page[address % PAGE_SIZE],
+            None => 0,
+        }
+    }
+
+    pub fn set(&mut self, address: usize, value: i64) {
+        self.check(address);
+        let page = self.pages[address / PAGE_SIZE].get_or_insert_with(|| Arc::new([0; PAGE_SIZE]));
+        Arc::make_mut(page)[address % PAGE_SIZE] = value;
+    }
+
+    pub fn to_vec(&self) -> Memory {
+        (0..self.len).map(|address| self.get(address)).collect()
+    }
+
+    /* Number of pages this copy owns outright, ie. that have been written since
+     * it was forked (or were never shared in the first place).
+     */
+    pub fn private_pages(&self) -> usize {
+        self.pages
+            .iter()
+            .flatten()
+            .filter(|page| Arc::strong_count(page) == 1)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let mut memory = PagedMemory::new(&[1, 2, 3], PAGE_SIZE * 4);
+        assert_eq!(memory.get(2), 3);
+        assert_eq!(memory.get(PAGE_SIZE * 3), 0);
+        assert_eq!(memory.private_pages(), 1);
+
+        memory.set(PAGE_SIZE * 3 + 1, 42);
+        assert_eq!(memory.get(PAGE_SIZE * 3 + 1), 42);
+        assert_eq!(memory.private_pages(), 2);
+        assert_eq!(memory.to_vec()[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut a = PagedMemory::new(&[7; PAGE_SIZE * 3], PAGE_SIZE * 3);
+        assert_eq!(a.private_pages(), 3);
+
+        let mut b = a.clone();
+        assert_eq!(a.private_pages(), 0);
+        assert_eq!(b.private_pages(), 0);
+
+        b.set(PAGE_SIZE + 5, 1);
+        b.set(PAGE_SIZE + 6, 2);
+        assert_eq!(b.private_pages(), 1);
+        assert_eq!(a.get(PAGE_SIZE + 5), 7);
+        assert_eq!(b.get(PAGE_SIZE + 5), 1);
+
+        a.set(0, 0);
+        assert_eq!(a.private_pages(), 2);
+        assert_eq!(b.get(0), 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_out_of_range() {
+        let memory = PagedMemory::new(&[1, 2, 3], 30);
+        memory.get(30);
+    }
+}
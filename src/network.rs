@@ -0,0 +1,278 @@
+use crate::{Machine, Memory, State, IO, VM};
+use std::fmt;
+
+pub type NodeId = usize;
+pub type SinkId = usize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Endpoint {
+    /* A node, and which of its inbound edges this is. */
+    Node(NodeId, usize),
+    Sink(SinkId),
+}
+
+struct Node {
+    name: String,
+    machine: Box<dyn Machine>,
+    consumers: Vec<Endpoint>,
+    /* What each producer has sent and the node hasn't yet taken, in the
+     * order they were connected.
+     */
+    inbound: Vec<(NodeId, IO)>,
+    /* The edge the next value is taken from. */
+    next: usize,
+    state: Option<State>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    /* Every machine halted without the sink producing another value. */
+    Halted,
+    /* Nothing can make progress; these machines are waiting for input. */
+    Deadlock(Vec<String>),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Halted => write!(f, "all machines halted"),
+            Stop::Deadlock(waiting) => write!(f, "deadlock: {} waiting", waiting.join(", ")),
+        }
+    }
+}
+
+/* A directed graph of VMs.  Each machine's output is copied to every
+ * consumer connected to it.  A machine with several producers takes one
+ * value from each in the order they were connected, waiting for the next
+ * one in turn rather than skipping ahead, so what it reads doesn't depend
+ * on how the machines are scheduled.  Producers which have halted, with
+ * nothing left to send, are passed over.  Values given with input() come
+ * before anything from the edges.
+ */
+#[derive(Default)]
+pub struct Network {
+    nodes: Vec<Node>,
+    sinks: Vec<IO>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, name: &str, program: &Memory) -> NodeId {
+        self.add_vm(name, VM::new(program))
+    }
+
+    pub fn add_vm(&mut self, name: &str, vm: VM) -> NodeId {
+        self.add_machine(name, Box::new(vm))
+    }
+
+    /* For machines other than a plain VM, such as transpiled programs. */
+    pub fn add_machine(&mut self, name: &str, machine: Box<dyn Machine>) -> NodeId {
+        self.nodes.push(Node {
+            name: name.to_string(),
+            machine,
+            consumers: Vec::new(),
+            inbound: Vec::new(),
+            next: 0,
+            state: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn input(&mut self, node: NodeId, value: i64) {
+        self.nodes[node].machine.vm_mut().input.push_back(value);
+    }
+
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        let edge = self.nodes[to].inbound.len();
+        self.nodes[to].inbound.push((from, IO::new()));
+        self.nodes[from].consumers.push(Endpoint::Node(to, edge));
+    }
+
+    pub fn add_sink(&mut self, from: NodeId) -> SinkId {
+        self.sinks.push(IO::new());
+        let sink = self.sinks.len() - 1;
+        self.nodes[from].consumers.push(Endpoint::Sink(sink));
+        sink
+    }
+
+    pub fn vm(&self, node: NodeId) -> &VM {
+        self.nodes[node].machine.vm()
+    }
+
+    fn deliver(&mut self, consumers: &[Endpoint], output: &IO) {
+        for consumer in consumers {
+            let queue = match consumer {
+                Endpoint::Node(j, edge) => &mut self.nodes[*j].inbound[*edge].1,
+                Endpoint::Sink(j) => &mut self.sinks[*j],
+            };
+            queue.extend(output.iter());
+        }
+    }
+
+    /* Moves what a node's producers have sent into its input, as far as it
+     * can in edge order.
+     */
+    fn feed(&mut self, i: NodeId) {
+        let edges = self.nodes[i].inbound.len();
+        let mut idle = 0;
+        while idle < edges {
+            let next = self.nodes[i].next;
+            let from = self.nodes[i].inbound[next].0;
+            let halted = self.nodes[from].state == Some(State::Halted);
+            let node = &mut self.nodes[i];
+            match node.inbound[next].1.pop_front() {
+                Some(value) => {
+                    node.machine.vm_mut().input.push_back(value);
+                    idle = 0;
+                }
+                None if halted => idle += 1,
+                None => return,
+            }
+            node.next = (next + 1) % edges;
+        }
+    }
+
+    /* Runs every machine in turn until the given sink has a value. */
+    pub fn run_until(&mut self, sink: SinkId) -> Result<i64, Stop> {
+        loop {
+            if let Some(value) = self.sinks[sink].pop_front() {
+                return Ok(value);
+            }
+
+            let mut progress = false;
+            for i in 0..self.nodes.len() {
+                self.feed(i);
+                let node = &mut self.nodes[i];
+                if let Some(State::Halted) = node.state {
+                    continue;
+                }
+                let before = node.machine.vm().steps();
+                node.state = Some(node.machine.run());
+                progress |= node.machine.vm().steps() != before;
+
+                let output = node.machine.vm_mut().drain_output();
+                let consumers = node.consumers.clone();
+                self.deliver(&consumers, &output);
+            }
+
+            if !progress && self.sinks[sink].is_empty() {
+                let waiting: Vec<String> = self
+                    .nodes
+                    .iter()
+                    .filter(|node| node.state == Some(State::NeedInput))
+                    .map(|node| node.name.clone())
+                    .collect();
+                return Err(if waiting.is_empty() {
+                    Stop::Halted
+                } else {
+                    Stop::Deadlock(waiting)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let program = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let mut network = Network::new();
+        let nodes: Vec<NodeId> = [4, 3, 2, 1, 0]
+            .iter()
+            .enumerate()
+            .map(|(i, &phase)| {
+                let node = network.add_node(&i.to_string(), &program);
+                network.input(node, phase);
+                node
+            })
+            .collect();
+        for pair in nodes.windows(2) {
+            network.connect(pair[0], pair[1]);
+        }
+        network.input(nodes[0], 0);
+        let sink = network.add_sink(nodes[4]);
+
+        assert_eq!(network.run_until(sink), Ok(43210));
+        assert_eq!(network.run_until(sink), Err(Stop::Halted));
+    }
+
+    #[test]
+    fn test_fan_out_fan_in() {
+        /* Reads x and y, and outputs 10x + y. */
+        let combine = vec![3, 15, 3, 16, 1002, 15, 10, 15, 1, 15, 16, 15, 4, 15, 99];
+        let mut network = Network::new();
+        let four = network.add_node("four", &vec![104, 4, 99]);
+        let seven = network.add_node("seven", &vec![104, 7, 99]);
+        let a = network.add_node("a", &combine);
+        let b = network.add_node("b", &combine);
+        network.connect(seven, a);
+        network.connect(four, a);
+        network.connect(seven, b);
+        network.connect(seven, b);
+        let sink_a = network.add_sink(a);
+        let sink_b = network.add_sink(b);
+
+        /* Producers are merged in connection order, not node order. */
+        assert_eq!(network.run_until(sink_a), Ok(74));
+        assert_eq!(network.run_until(sink_b), Ok(77));
+    }
+
+    #[test]
+    fn test_fan_in_rates() {
+        /* Sends everything at once, and halts. */
+        let fast = vec![104, 1, 104, 2, 104, 3, 99];
+        /* Outputs ten times each value it reads. */
+        let slow = vec![3, 11, 1002, 11, 10, 11, 4, 11, 1105, 1, 0, 0];
+        /* Outputs each value it reads. */
+        let echo = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+        let mut network = Network::new();
+        let fast_node = network.add_node("fast", &fast);
+        let slow_node = network.add_node("slow", &slow);
+        let merge = network.add_node("merge", &echo);
+        network.connect(fast_node, slow_node);
+        network.connect(fast_node, merge);
+        network.connect(slow_node, merge);
+        network.input(slow_node, 4);
+        let sink = network.add_sink(merge);
+
+        /* One from each in turn, even though all of fast's output is
+         * there first, and only slow's once fast has halted.
+         */
+        let merged: Vec<i64> = (0..7).map(|_| network.run_until(sink).unwrap()).collect();
+        assert_eq!(merged, [1, 40, 2, 10, 3, 20, 30]);
+        assert_eq!(
+            network.run_until(sink),
+            Err(Stop::Deadlock(vec![
+                String::from("slow"),
+                String::from("merge")
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_deadlock() {
+        /* Echoes one value, then waits for another before halting. */
+        let echo = vec![3, 7, 4, 7, 3, 7, 99, 0];
+        let mut network = Network::new();
+        let ping = network.add_node("ping", &echo);
+        let pong = network.add_node("pong", &echo);
+        let halt = network.add_node("halt", &vec![99]);
+        network.connect(ping, pong);
+        network.connect(pong, ping);
+        network.input(ping, 1);
+        let sink = network.add_sink(halt);
+
+        assert_eq!(
+            network.run_until(sink),
+            Err(Stop::Deadlock(vec![String::from("pong")]))
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use crate::analysis::{Analysis, Instruction};
+use crate::VM;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+/* Callbacks from a running VM.  Every method does nothing by default, so an
+ * observer only implements what it cares about.
+ *
+ * Instruction callbacks see the VM with ip at the instruction: before it
+ * runs, and after it completes (by which time ip has moved on, so the
+ * address is passed in).  An instruction stopped for input never completes,
+ * and runs again once input arrives, but before_instruction is only called
+ * the first time.  Likewise on_halt is only called once, however often a
+ * halted VM is run.
+ */
+pub trait Observer: Any + Send {
+    fn before_instruction(&mut self, _vm: &VM) {}
+    fn after_instruction(&mut self, _vm: &VM, _ip: usize) {}
+    fn on_input(&mut self, _vm: &VM, _value: i64) {}
+    fn on_output(&mut self, _vm: &VM, _value: i64) {}
+    fn on_halt(&mut self, _vm: &VM) {}
+    /* Called once the value has been stored. */
+    fn on_memory_write(&mut self, _vm: &VM, _address: usize, _old: i64, _new: i64) {}
+}
+
+/* The observers installed on a VM.  A fork starts with none: observers
+ * hold state about one run, which can't be split in two.
+ */
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn Observer>>);
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Observers {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, observer: Box<dyn Observer>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn get<T: Observer>(&self) -> Option<&T> {
+        self.0
+            .iter()
+            .find_map(|o| (o.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    pub(crate) fn take<T: Observer>(&mut self) -> Option<T> {
+        let i = self
+            .0
+            .iter()
+            .position(|o| (o.as_ref() as &dyn Any).is::<T>())?;
+        let observer: Box<dyn Any> = self.0.remove(i);
+        observer.downcast::<T>().ok().map(|o| *o)
+    }
+}
+
+/* Calls f on each observer.  Observers are taken out of the VM meanwhile,
+ * so they can look at it.
+ */
+pub(crate) fn notify<F>(vm: &mut VM, mut f: F)
+where
+    F: FnMut(&mut dyn Observer, &VM),
+{
+    let mut observers = std::mem::take(&mut vm.observers);
+    for observer in observers.0.iter_mut() {
+        f(observer.as_mut(), vm);
+    }
+    vm.observers = observers;
+}
+
+/* The instruction at ip, decoded from however much of it is in memory. */
+fn instruction(vm: &VM, ip: usize) -> Option<Instruction> {
+    let memory = vm.memory();
+    let end = memory.len().min(ip + 4);
+    let cells: Vec<i64> = (ip..end).map(|a| memory.get(a)).collect();
+    Instruction::decode(&cells, 0)
+}
+
+/* Writes a line per instruction, and per value in or out. */
+pub struct Tracer<W: Write + Send + 'static> {
+    out: W,
+}
+
+impl Tracer<io::Stderr> {
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+    }
+}
+
+impl<W: Write + Send + 'static> Tracer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send + 'static> Observer for Tracer<W> {
+    fn before_instruction(&mut self, vm: &VM) {
+        let ip = vm.ip;
+        let text = match instruction(vm, ip) {
+            Some(instruction) => instruction.to_string(),
+            None => format!("data {}", vm.memory().get(ip)),
+        };
+        writeln!(
+            self.out,
+            "{:>8} {:>5}  {:<32} rb={}",
+            vm.steps(),
+            ip,
+            text,
+            vm.relative_base
+        )
+        .unwrap();
+    }
+
+    fn on_input(&mut self, _vm: &VM, value: i64) {
+        writeln!(self.out, "{:>14}  in {}", "", value).unwrap();
+    }
+
+    fn on_output(&mut self, _vm: &VM, value: i64) {
+        writeln!(self.out, "{:>14}  out {}", "", value).unwrap();
+    }
+
+    fn on_halt(&mut self, vm: &VM) {
+        writeln!(self.out, "{:>8} halted", vm.steps()).unwrap();
+    }
+}
+
+/* Counts how often each instruction runs. */
+#[derive(Debug, Default)]
+pub struct Profiler {
+    by_address: BTreeMap<usize, u64>,
+    by_opcode: BTreeMap<i64, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&self, address: usize) -> u64 {
+        self.by_address.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn by_opcode(&self) -> &BTreeMap<i64, u64> {
+        &self.by_opcode
+    }
+
+    /* The n most executed addresses, busiest first. */
+    pub fn hottest(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut counts: Vec<(usize, u64)> = self.by_address.iter().map(|(&a, &c)| (a, c)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    /* The hottest instructions, disassembled from the program. */
+    pub fn report(&self, program: &[i64], n: usize) -> String {
+        let mut s = String::new();
+        for (address, count) in self.hottest(n) {
+            let text = Instruction::decode(program, address)
+                .map(|i| i.to_string())
+                .unwrap_or_default();
+            s.push_str(&format!("{:>10} {:>5}  {}\n", count, address, text));
+        }
+        s
+    }
+
+    fn count_at(&mut self, vm: &VM, ip: usize) {
+        *self.by_address.entry(ip).or_insert(0) += 1;
+        *self.by_opcode.entry(vm.memory().get(ip) % 100).or_insert(0) += 1;
+    }
+}
+
+/* Only completed instructions count, so one waiting for input isn't counted
+ * each time it's retried.
+ */
+impl Observer for Profiler {
+    fn after_instruction(&mut self, vm: &VM, ip: usize) {
+        self.count_at(vm, ip);
+    }
+
+    fn on_halt(&mut self, vm: &VM) {
+        self.count_at(vm, vm.ip);
+    }
+}
+
+/* Which instructions have run, and which cells have been written. */
+#[derive(Debug, Default)]
+pub struct Coverage {
+    executed: BTreeSet<usize>,
+    written: BTreeSet<usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn executed(&self) -> &BTreeSet<usize> {
+        &self.executed
+    }
+
+    pub fn written(&self) -> &BTreeSet<usize> {
+        &self.written
+    }
+
+    /* Instructions found by static analysis which never ran. */
+    pub fn missed<'a>(&self, analysis: &'a Analysis) -> Vec<&'a Instruction> {
+        analysis
+            .instructions
+            .values()
+            .filter(|i| !self.executed.contains(&i.address))
+            .collect()
+    }
+
+    pub fn report(&self, program: &[i64]) -> String {
+        let analysis = Analysis::new(program);
+        let missed = self.missed(&analysis);
+        let mut s = format!(
+            "{} of {} instructions executed, {} cells written\n",
+            analysis.instructions.len() - missed.len(),
+            analysis.instructions.len(),
+            self.written.len()
+        );
+        for instruction in missed {
+            s.push_str(&format!("{:>5}  {}\n", instruction.address, instruction));
+        }
+        s
+    }
+}
+
+impl Observer for Coverage {
+    fn before_instruction(&mut self, vm: &VM) {
+        self.executed.insert(vm.ip);
+    }
+
+    fn on_memory_write(&mut self, _vm: &VM, address: usize, _old: i64, _new: i64) {
+        self.written.insert(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    /* Remembers every callback, in order. */
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl Observer for Log {
+        fn before_instruction(&mut self, vm: &VM) {
+            self.0.push(format!("before {}", vm.ip));
+        }
+        fn after_instruction(&mut self, vm: &VM, ip: usize) {
+            self.0.push(format!("after {} -> {}", ip, vm.ip));
+        }
+        fn on_input(&mut self, _vm: &VM, value: i64) {
+            self.0.push(format!("in {}", value));
+        }
+        fn on_output(&mut self, _vm: &VM, value: i64) {
+            self.0.push(format!("out {}", value));
+        }
+        fn on_halt(&mut self, vm: &VM) {
+            self.0.push(format!("halt {}", vm.steps()));
+        }
+        fn on_memory_write(&mut self, _vm: &VM, address: usize, old: i64, new: i64) {
+            self.0.push(format!("write {} {} -> {}", address, old, new));
+        }
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let mut vm = VM::new(&vec![3, 5, 4, 5, 99, 0]);
+        vm.observe(Log::default());
+        assert_eq!(vm.run(), State::NeedInput);
+        vm.input.push_back(7);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(
+            vm.take_observer::<Log>().unwrap().0,
+            vec![
+                "before 0",
+                "in 7",
+                "write 5 0 -> 7",
+                "after 0 -> 2",
+                "before 2",
+                "out 7",
+                "after 2 -> 4",
+                "before 4",
+                "halt 2",
+            ]
+        );
+        assert!(vm.observer::<Log>().is_none());
+    }
+
+    #[test]
+    fn test_retries() {
+        /* Waiting for input, or running again once halted, is one
+         * instruction as far as observers are concerned.
+         */
+        let mut vm = VM::new(&vec![3, 3, 99, 0]);
+        vm.observe(Log::default());
+        vm.observe(Profiler::new());
+        assert_eq!(vm.run(), State::NeedInput);
+        assert_eq!(vm.run(), State::NeedInput);
+        vm.input.push_back(1);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.observer::<Profiler>().unwrap().by_opcode()[&99], 1);
+        assert_eq!(
+            vm.take_observer::<Log>().unwrap().0,
+            vec![
+                "before 0",
+                "in 1",
+                "write 3 0 -> 1",
+                "after 0 -> 2",
+                "before 2",
+                "halt 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_several() {
+        /* Counts down from 3, outputting each value. */
+        let program = vec![4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3];
+        let mut vm = VM::new(&program);
+        vm.observe(Profiler::new());
+        vm.observe(Coverage::new());
+        vm.observe(Tracer::new(Vec::new()));
+        vm.run();
+        assert_eq!(vm.output, vec![3, 2, 1]);
+
+        let profiler = vm.observer::<Profiler>().unwrap();
+        assert_eq!(profiler.count(0), 3);
+        assert_eq!(profiler.count(9), 1);
+        assert_eq!(profiler.count(10), 0);
+        assert_eq!(profiler.by_opcode()[&99], 1);
+        assert_eq!(profiler.hottest(1), vec![(0, 3)]);
+
+        let coverage = vm.observer::<Coverage>().unwrap();
+        assert_eq!(coverage.executed().len(), 4);
+        assert_eq!(coverage.written().iter().collect::<Vec<_>>(), vec![&10]);
+        assert!(coverage.report(&program).starts_with("4 of 4"));
+
+        let tracer = vm.take_observer::<Tracer<Vec<u8>>>().unwrap();
+        let trace = String::from_utf8(tracer.into_inner()).unwrap();
+        assert_eq!(trace.lines().count(), 10 + 3 + 1);
+        assert!(trace.lines().next().unwrap().contains("out [10]"));
+
+        /* Forks don't inherit observers. */
+        assert!(vm.fork().observer::<Profiler>().is_none());
+    }
+}
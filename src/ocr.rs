@@ -0,0 +1,176 @@
+use crate::grid::{DenseGrid, Grid, Point};
+use std::fmt;
+
+/* The letters Advent of Code draws with, each 4 pixels wide and 6 high
+ * with a blank column between them.
+ */
+const FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const WIDTH: usize = 4;
+const HEIGHT: usize = 6;
+
+/* Some of the picture couldn't be read. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unrecognised {
+    /* What could be read, with a '?' for each glyph which couldn't. */
+    pub text: String,
+    /* Those glyphs, with where they are in the text, as rows of '#' and
+     * '.'.
+     */
+    pub glyphs: Vec<(usize, Vec<String>)>,
+}
+
+impl fmt::Display for Unrecognised {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "read {:?}, but not", self.text)?;
+        for (i, rows) in &self.glyphs {
+            write!(f, "\nglyph {}:", i)?;
+            for row in rows {
+                write!(f, "\n  {}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Unrecognised {}
+
+/* Reads the letters in a picture, where true is a lit pixel.  Blank rows
+ * and columns around the letters are ignored.
+ */
+pub fn read(picture: &DenseGrid<bool>) -> Result<String, Unrecognised> {
+    let lit = |x: usize, y: usize| {
+        picture
+            .get(Point::new(x as i64, y as i64))
+            .copied()
+            .unwrap_or(false)
+    };
+    let column = |x: usize| (0..picture.height()).any(|y| lit(x, y));
+    let row = |y: usize| (0..picture.width()).any(|x| lit(x, y));
+    let left = match (0..picture.width()).find(|&x| column(x)) {
+        Some(x) => x,
+        None => return Ok(String::new()),
+    };
+    let right = (0..picture.width()).rev().find(|&x| column(x)).unwrap();
+    let top = (0..picture.height()).find(|&y| row(y)).unwrap();
+
+    let attempt = |start: usize| {
+        let mut text = String::new();
+        let mut glyphs = Vec::new();
+        for (i, x0) in (start..=right).step_by(WIDTH + 1).enumerate() {
+            let rows: Vec<String> = (top..top + HEIGHT)
+                .map(|y| {
+                    (x0..x0 + WIDTH)
+                        .map(|x| if lit(x, y) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            match FONT.iter().find(|(_, glyph)| glyph[..] == rows[..]) {
+                Some(&(c, _)) => text.push(c),
+                None => {
+                    text.push('?');
+                    glyphs.push((i, rows));
+                }
+            }
+        }
+        Unrecognised { text, glyphs }
+    };
+    /* The first lit column is usually the first of a glyph, but an I has
+     * a blank one before it, so the glyphs may start one further left.
+     * Whichever reads more of the text wins.
+     */
+    let best = (0..=left.min(1))
+        .map(|offset| attempt(left - offset))
+        .min_by_key(|attempt| attempt.glyphs.len())
+        .unwrap();
+    if best.glyphs.is_empty() {
+        Ok(best.text)
+    } else {
+        Err(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* The letters side by side, as a picture. */
+    fn picture(rows: &[&str]) -> DenseGrid<bool> {
+        DenseGrid::from_rows(
+            rows.iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_font() {
+        let rows: Vec<String> = (0..HEIGHT)
+            .map(|y| {
+                let glyphs: Vec<&str> = FONT.iter().map(|(_, g)| g[y]).collect();
+                format!("..{}...", glyphs.join("."))
+            })
+            .collect();
+        let mut rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let blank = ".".repeat(rows[0].len());
+        rows.insert(0, &blank);
+        rows.push(&blank);
+        let letters: String = FONT.iter().map(|(c, _)| c).collect();
+        assert_eq!(read(&picture(&rows)), Ok(letters));
+        assert_eq!(read(&picture(&[&blank])), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_leading_i() {
+        let rows = [
+            ".###.#..#.####", //
+            "..#..#..#.#...", //
+            "..#..####.###.", //
+            "..#..#..#.#...", //
+            "..#..#..#.#...", //
+            ".###.#..#.####", //
+        ];
+        assert_eq!(read(&picture(&rows)), Ok(String::from("IHE")));
+        let shifted: Vec<String> = rows.iter().map(|row| format!("..{}", row)).collect();
+        let shifted: Vec<&str> = shifted.iter().map(String::as_str).collect();
+        assert_eq!(read(&picture(&shifted)), Ok(String::from("IHE")));
+    }
+
+    #[test]
+    fn test_unrecognised() {
+        let rows = [
+            "#..#.#..#", //
+            "#..#.##.#", //
+            "####.#.##", //
+            "#..#.#..#", //
+            "#..#.#..#", //
+            "#..#.#..#", //
+        ];
+        let e = read(&picture(&rows)).unwrap_err();
+        assert_eq!(e.text, "H?");
+        assert_eq!(e.glyphs.len(), 1);
+        assert_eq!(e.glyphs[0].0, 1);
+        assert_eq!(e.glyphs[0].1[1], "##.#");
+        assert!(e
+            .to_string()
+            .starts_with("read \"H?\", but not\nglyph 1:\n  #..#\n  ##.#"));
+    }
+}
@@ -0,0 +1,389 @@
+use crate::observe;
+use crate::replay::Event;
+use crate::strict::{self, Violation};
+use crate::{State, VM};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Read,
+    Write,
+}
+
+/* What an instruction does next once its handler has run. */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Flow {
+    /* Carry on with the following instruction. */
+    Next,
+    Jump(usize),
+    /* Stop without completing the instruction, so it runs again next time. */
+    Stop(State),
+}
+
+/* The instruction being executed, as seen by its handler.  Parameters are
+ * numbered from 0, and must be used as the opcode's roles declare.
+ */
+pub struct Call<'a> {
+    vm: &'a mut VM,
+    opcode: &'a Opcode,
+}
+
+impl<'a> Call<'a> {
+    fn check(&self, i: usize, role: Role) {
+        if self.opcode.roles.get(i) != Some(&role) {
+            panic!(
+                "ip {}: {} parameter {} is not a {:?} parameter",
+                self.vm.ip, self.opcode.name, i, role
+            );
+        }
+    }
+
+    /* The value of a read parameter. */
+    pub fn arg(&self, i: usize) -> i64 {
+        self.check(i, Role::Read);
+        self.vm.arg(i as u32 + 1)
+    }
+
+    /* Stores through a write parameter. */
+    pub fn set(&mut self, i: usize, value: i64) {
+        self.check(i, Role::Write);
+        self.vm.set_arg(i as u32 + 1, value)
+    }
+
+    pub fn input(&mut self) -> Option<i64> {
+        let value = self.vm.input.pop_front()?;
+        let step = self.vm.steps;
+        self.vm.log(Event::Input { step, value });
+        if !self.vm.observers.is_empty() {
+            observe::notify(self.vm, |o, vm| o.on_input(vm, value));
+        }
+        Some(value)
+    }
+
+    pub fn output(&mut self, value: i64) {
+        self.vm.output.push_back(value);
+        let step = self.vm.steps;
+        self.vm.log(Event::Output { step, value });
+        if !self.vm.observers.is_empty() {
+            observe::notify(self.vm, |o, vm| o.on_output(vm, value));
+        }
+    }
+
+    pub fn adjust_base(&mut self, offset: i64) {
+        self.vm.relative_base += offset;
+    }
+
+    pub fn ip(&self) -> usize {
+        self.vm.ip
+    }
+}
+
+pub type Handler = Arc<dyn Fn(&mut Call) -> Flow + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Opcode {
+    pub code: i64,
+    pub name: String,
+    pub roles: Vec<Role>,
+    pub handler: Handler,
+}
+
+impl Opcode {
+    pub fn arity(&self) -> usize {
+        self.roles.len()
+    }
+}
+
+impl fmt::Debug for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {:?}", self.code, self.name, self.roles)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    /* Opcodes are the last two digits of an instruction. */
+    OutOfRange(i64),
+    CodeTaken { code: i64, by: String },
+    NameTaken { name: String, by: i64 },
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegisterError::OutOfRange(code) => write!(f, "opcode {} is not in 1..=99", code),
+            RegisterError::CodeTaken { code, by } => {
+                write!(f, "opcode {} is already {}", code, by)
+            }
+            RegisterError::NameTaken { name, by } => {
+                write!(f, "{} is already opcode {}", name, by)
+            }
+        }
+    }
+}
+
+/* The instructions a VM understands, indexed by opcode. */
+#[derive(Clone, Debug)]
+pub struct OpcodeTable {
+    opcodes: Vec<Option<Opcode>>,
+}
+
+impl Default for OpcodeTable {
+    fn default() -> Self {
+        Self {
+            opcodes: vec![None; 100],
+        }
+    }
+}
+
+impl OpcodeTable {
+    /* A table with no instructions at all. */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /* The standard instruction set. */
+    pub fn builtin() -> Self {
+        use Role::{Read, Write};
+
+        let mut table = Self::new();
+        let mut add = |code, name, roles: &[Role], handler: fn(&mut Call) -> Flow| {
+            table
+                .register(code, name, roles, handler)
+                .expect("built-in opcodes are distinct");
+        };
+        add(1, "add", &[Read, Read, Write], |c| {
+            let value = c.arg(0) + c.arg(1);
+            c.set(2, value);
+            Flow::Next
+        });
+        add(2, "mul", &[Read, Read, Write], |c| {
+            let value = c.arg(0) * c.arg(1);
+            c.set(2, value);
+            Flow::Next
+        });
+        add(3, "in", &[Write], |c| match c.input() {
+            Some(value) => {
+                c.set(0, value);
+                Flow::Next
+            }
+            None => Flow::Stop(State::NeedInput),
+        });
+        add(4, "out", &[Read], |c| {
+            let value = c.arg(0);
+            c.output(value);
+            Flow::Next
+        });
+        add(5, "jnz", &[Read, Read], |c| {
+            if c.arg(0) != 0 {
+                Flow::Jump(c.arg(1) as usize)
+            } else {
+                Flow::Next
+            }
+        });
+        add(6, "jz", &[Read, Read], |c| {
+            if c.arg(0) == 0 {
+                Flow::Jump(c.arg(1) as usize)
+            } else {
+                Flow::Next
+            }
+        });
+        add(7, "lt", &[Read, Read, Write], |c| {
+            let value = (c.arg(0) < c.arg(1)) as i64;
+            c.set(2, value);
+            Flow::Next
+        });
+        add(8, "eq", &[Read, Read, Write], |c| {
+            let value = (c.arg(0) == c.arg(1)) as i64;
+            c.set(2, value);
+            Flow::Next
+        });
+        add(9, "arb", &[Read], |c| {
+            let offset = c.arg(0);
+            c.adjust_base(offset);
+            Flow::Next
+        });
+        add(99, "halt", &[], |_| Flow::Stop(State::Halted));
+        table
+    }
+
+    /* The shared built-in table VM::new() uses. */
+    pub fn shared() -> Arc<Self> {
+        static BUILTIN: OnceLock<Arc<OpcodeTable>> = OnceLock::new();
+        BUILTIN.get_or_init(|| Arc::new(Self::builtin())).clone()
+    }
+
+    pub fn register<F>(
+        &mut self,
+        code: i64,
+        name: &str,
+        roles: &[Role],
+        handler: F,
+    ) -> Result<(), RegisterError>
+    where
+        F: Fn(&mut Call) -> Flow + Send + Sync + 'static,
+    {
+        if !(1..=99).contains(&code) {
+            return Err(RegisterError::OutOfRange(code));
+        }
+        if let Some(existing) = self.get(code) {
+            return Err(RegisterError::CodeTaken {
+                code,
+                by: existing.name.clone(),
+            });
+        }
+        if let Some(existing) = self.iter().find(|op| op.name == name) {
+            return Err(RegisterError::NameTaken {
+                name: name.to_string(),
+                by: existing.code,
+            });
+        }
+        self.opcodes[code as usize] = Some(Opcode {
+            code,
+            name: name.to_string(),
+            roles: roles.to_vec(),
+            handler: Arc::new(handler),
+        });
+        Ok(())
+    }
+
+    pub fn get(&self, code: i64) -> Option<&Opcode> {
+        self.opcodes.get(code as usize)?.as_ref()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Opcode> {
+        self.opcodes.iter().flatten()
+    }
+}
+
+/* Runs the instruction at ip through the VM's opcode table, checking it
+ * first and afterwards if the VM is in strict mode, and telling any
+ * observers, once however many times it's retried.
+ */
+pub(crate) fn execute(vm: &mut VM, opcodes: &OpcodeTable) -> Result<Option<State>, Violation> {
+    let ip = vm.ip;
+    let code = vm.memory.get(ip) % 100;
+    let opcode = match opcodes.get(code) {
+        Some(opcode) => opcode,
+        None => panic!("the discotheque: ip {}: {}", ip, code),
+    };
+    if vm.checker.is_some() {
+        strict::before(vm, opcode)?;
+    }
+    let observed = !vm.observers.is_empty();
+    let stopped = vm.stopped;
+    if observed && stopped.is_none() {
+        observe::notify(vm, |o, vm| o.before_instruction(vm));
+    }
+    let flow = (opcode.handler)(&mut Call {
+        vm: &mut *vm,
+        opcode,
+    });
+    if vm.checker.is_some() {
+        strict::after(vm, ip, flow)?;
+    }
+    match flow {
+        Flow::Next => vm.ip += 1 + opcode.arity(),
+        Flow::Jump(target) => vm.ip = target,
+        Flow::Stop(state) => {
+            if observed && state == State::Halted && stopped != Some(state) {
+                observe::notify(vm, |o, vm| o.on_halt(vm));
+            }
+            vm.stopped = Some(state);
+            return Ok(Some(state));
+        }
+    }
+    vm.stopped = None;
+    vm.steps += 1;
+    if observed {
+        observe::notify(vm, |o, vm| o.after_instruction(vm, ip));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_conflicts() {
+        let mut table = OpcodeTable::builtin();
+        assert_eq!(
+            table.register(1, "plus", &[Role::Read], |_| Flow::Next),
+            Err(RegisterError::CodeTaken {
+                code: 1,
+                by: String::from("add")
+            })
+        );
+        assert_eq!(
+            table.register(10, "out", &[Role::Read], |_| Flow::Next),
+            Err(RegisterError::NameTaken {
+                name: String::from("out"),
+                by: 4
+            })
+        );
+        assert_eq!(
+            table.register(100, "big", &[], |_| Flow::Next),
+            Err(RegisterError::OutOfRange(100))
+        );
+        assert_eq!(table.register(10, "nop", &[], |_| Flow::Next), Ok(()));
+        assert_eq!(table.iter().count(), 11);
+    }
+
+    #[test]
+    fn test_custom() {
+        /* A debug print which doesn't touch the output queue, and a trap
+         * which halts if its operand is non-zero.
+         */
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let log = printed.clone();
+        let mut table = OpcodeTable::builtin();
+        table
+            .register(42, "dbg", &[Role::Read], move |c| {
+                log.lock().unwrap().push(c.arg(0));
+                Flow::Next
+            })
+            .unwrap();
+        table
+            .register(50, "trap", &[Role::Read], |c| {
+                if c.arg(0) != 0 {
+                    Flow::Stop(State::Halted)
+                } else {
+                    Flow::Next
+                }
+            })
+            .unwrap();
+
+        /* Prints and decrements 20 until the trap fires when it reaches 0. */
+        let program = vec![
+            42, 20, 1001, 20, -1, 20, 1007, 20, 1, 21, 50, 21, 1105, 1, 0, 99, 0, 0, 0, 0, 3, 0,
+        ];
+        let mut vm = VM::with_opcodes(&program, table);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(*printed.lock().unwrap(), vec![3, 2, 1]);
+        assert_eq!(vm.memory().get(20), 0);
+        assert_eq!(vm.output, vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ip 0: sto parameter 0 is not a Write parameter")]
+    fn test_roles() {
+        /* Declared as reading its operand, but writes through it. */
+        let mut table = OpcodeTable::builtin();
+        table
+            .register(10, "sto", &[Role::Read], |c| {
+                c.set(0, 1);
+                Flow::Next
+            })
+            .unwrap();
+        VM::with_opcodes(&vec![10, 3, 99, 0], table).run();
+    }
+
+    #[test]
+    #[should_panic(expected = "the discotheque")]
+    fn test_unknown() {
+        VM::with_opcodes(&vec![1101, 1, 1, 0, 99], OpcodeTable::new()).run();
+    }
+}
@@ -0,0 +1,512 @@
+use crate::analysis::{Analysis, Instruction, Mode, Op, Param};
+use crate::workload::{self, Driver};
+use crate::{Memory, VM};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /* Operands read from cells nothing writes, replaced by their values. */
+    pub propagated: usize,
+    pub folded: usize,
+    pub moves: usize,
+    pub jumps: usize,
+    pub threaded: usize,
+    pub removed: usize,
+    /* Reachable instructions left alone because they might be modified or
+     * read as data.
+     */
+    pub skipped: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} operands propagated, {} constants folded, {} moves, {} constant jumps, \
+             {} jumps threaded, {} instructions removed, {} skipped",
+            self.propagated,
+            self.folded,
+            self.moves,
+            self.jumps,
+            self.threaded,
+            self.removed,
+            self.skipped
+        )
+    }
+}
+
+fn mov(address: usize, src: Param, dst: Param) -> Instruction {
+    Instruction {
+        address,
+        op: Op::Add,
+        params: vec![src, Param::immediate(0), dst],
+    }
+}
+
+fn jump(address: usize, target: Param) -> Instruction {
+    Instruction {
+        address,
+        op: Op::JumpIfTrue,
+        params: vec![Param::immediate(1), target],
+    }
+}
+
+fn is_move(i: &Instruction) -> bool {
+    i.op == Op::Add && i.params[1] == Param::immediate(0)
+}
+
+/* Instructions which have no effect when executed. */
+fn is_noop(i: &Instruction) -> bool {
+    match i.op {
+        Op::JumpIfTrue | Op::JumpIfFalse => {
+            i.taken() == Some(false)
+                || (i.taken() == Some(true) && i.params[1] == Param::immediate(i.next() as i64))
+        }
+        _ => is_move(i) && i.params[0] == i.params[2] && i.params[0].mode != Mode::Immediate,
+    }
+}
+
+/* Rewrites a single instruction into a canonical equivalent of the same
+ * length: constant arithmetic becomes a move of the result, multiplying by 1
+ * or adding 0 becomes a move, and jumps on a true constant condition become
+ * unconditional.  Jumps on a false one are already no-ops, which compact()
+ * removes.
+ */
+fn rewrite(i: &Instruction, stats: &mut Stats) -> Instruction {
+    match i.op {
+        Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+            let (a, b, dst) = (i.params[0], i.params[1], i.params[2]);
+            if let Some(value) = i.constant_result() {
+                let folded = mov(i.address, Param::immediate(value), dst);
+                if folded != *i {
+                    stats.folded += 1;
+                }
+                return folded;
+            }
+            let src = match (i.op, a.constant(), b.constant()) {
+                (Op::Add, Some(0), _) => Some(b),
+                (Op::Mul, Some(1), _) => Some(b),
+                (Op::Mul, _, Some(1)) => Some(a),
+                (Op::Mul, Some(0), _) | (Op::Mul, _, Some(0)) => Some(Param::immediate(0)),
+                _ => None,
+            };
+            match src {
+                Some(src) if !is_move(i) => {
+                    stats.moves += 1;
+                    mov(i.address, src, dst)
+                }
+                _ => i.clone(),
+            }
+        }
+        Op::JumpIfTrue | Op::JumpIfFalse => match i.taken() {
+            Some(true) if i.op != Op::JumpIfTrue || i.params[0] != Param::immediate(1) => {
+                stats.jumps += 1;
+                jump(i.address, i.params[1])
+            }
+            _ => i.clone(),
+        },
+        _ => i.clone(),
+    }
+}
+
+struct Optimizer {
+    analysis: Analysis,
+    /* The instructions we're allowed to change. */
+    code: BTreeMap<usize, Instruction>,
+    /* The cells with known values at each jump. */
+    known: BTreeMap<usize, BTreeMap<usize, i64>>,
+    stats: Stats,
+}
+
+impl Optimizer {
+    /* Only instructions which nothing can overwrite or read as data may be
+     * changed.
+     */
+    fn safe(&self, i: &Instruction) -> bool {
+        !self.analysis.may_be_modified(i) && !self.analysis.may_be_read(i)
+    }
+
+    /* Replaces operands with their values where they're known: cells
+     * nothing writes, and cells given a constant earlier in the same block.
+     */
+    fn propagate(&mut self, i: &Instruction, known: &BTreeMap<usize, i64>) -> Instruction {
+        let mut i = i.clone();
+        for (n, param) in i.params.iter_mut().enumerate() {
+            if i.op.output() == Some(n) || param.mode == Mode::Immediate {
+                continue;
+            }
+            let value = match self.analysis.address(param) {
+                Some(address) if known.contains_key(&address) => Some(known[&address]),
+                _ => self.analysis.constant(param),
+            };
+            if let Some(value) = value {
+                *param = Param::immediate(value);
+                self.stats.propagated += 1;
+            }
+        }
+        i
+    }
+
+    /* Walks the code in order, keeping track of the cells written with
+     * constants since the last place a jump might land.
+     */
+    fn rewrite(&mut self) {
+        let targets = self.targets().unwrap_or_default();
+        let instructions: Vec<Instruction> = self.analysis.instructions.values().cloned().collect();
+        let mut known = BTreeMap::new();
+        let mut expected = None;
+        for i in &instructions {
+            if targets.contains(&i.address) || expected != Some(i.address) {
+                known.clear();
+            }
+            expected = Some(i.next());
+            if !self.safe(i) {
+                self.stats.skipped += 1;
+                known.clear();
+                continue;
+            }
+            let propagated = self.propagate(i, &known);
+            if i.is_jump() {
+                self.known.insert(i.address, known.clone());
+            }
+            if let Some(n) = i.op.output() {
+                match self.analysis.address(&i.params[n]) {
+                    Some(address) => match propagated.constant_result() {
+                        Some(value) => known.insert(address, value),
+                        None => known.remove(&address),
+                    },
+                    None => {
+                        known.clear();
+                        None
+                    }
+                };
+            }
+            self.code
+                .insert(i.address, rewrite(&propagated, &mut self.stats));
+        }
+    }
+
+    /* Where execution goes from a jump, given the cells known when it's
+     * taken, if that's somewhere else: through it if it's unconditional or
+     * its condition is known.
+     */
+    fn follow(&self, address: usize, known: &BTreeMap<usize, i64>) -> Option<usize> {
+        let i = self.code.get(&address)?;
+        if !i.is_jump() || self.analysis.may_be_modified(i) {
+            return None;
+        }
+        let mut i = i.clone();
+        if let Some(&value) = self
+            .analysis
+            .address(&i.params[0])
+            .and_then(|a| known.get(&a))
+        {
+            i.params[0] = Param::immediate(value);
+        }
+        match i.taken()? {
+            true => i.params[1].constant().map(|t| t as usize),
+            false => Some(i.next()),
+        }
+    }
+
+    /* A jump to another jump which is sure to be taken, or sure not to be,
+     * can go straight to where that one leads.
+     */
+    fn thread_jumps(&mut self) {
+        let jumps: Vec<usize> = self
+            .code
+            .values()
+            .filter(|i| i.is_jump() && i.taken() != Some(false))
+            .map(|i| i.address)
+            .collect();
+        let none = BTreeMap::new();
+        for address in jumps {
+            let target = match self.code[&address].params[1].constant() {
+                Some(t) => t as usize,
+                None => continue,
+            };
+            let known = self.known.get(&address).unwrap_or(&none);
+            let mut seen = BTreeSet::new();
+            let mut end = target;
+            while let Some(next) = self.follow(end, known) {
+                if !seen.insert(end) {
+                    break;
+                }
+                end = next;
+            }
+            if end != target {
+                self.code.get_mut(&address).unwrap().params[1] = Param::immediate(end as i64);
+                self.stats.threaded += 1;
+            }
+        }
+    }
+
+    /* Everywhere a jump might land, or None if a jump target could be
+     * overwritten.  Jumps through memory could go anywhere the program has
+     * the address of, so every value in the program counts.
+     */
+    fn targets(&self) -> Option<BTreeSet<usize>> {
+        let mut targets = self.analysis.entries.clone();
+        targets.extend(
+            self.analysis
+                .program
+                .iter()
+                .filter(|&&x| x >= 0)
+                .map(|&x| x as usize),
+        );
+        for (address, original) in &self.analysis.instructions {
+            let i = self.code.get(address).unwrap_or(original);
+            if !i.is_jump() || i.taken() == Some(false) {
+                continue;
+            }
+            if self.analysis.may_be_written(i.address + 2) {
+                return None;
+            }
+            if let Some(target) = i.params[1].constant() {
+                targets.insert(target as usize);
+            }
+        }
+        Some(targets)
+    }
+
+    /* Straight-line runs of safe code entered only at the top. */
+    fn blocks(&self) -> Vec<Vec<usize>> {
+        let targets = match self.targets() {
+            Some(targets) => targets,
+            None => return Vec::new(),
+        };
+        let mut blocks: Vec<Vec<usize>> = Vec::new();
+        let mut expected = None;
+        for (&address, i) in &self.code {
+            if targets.contains(&address) || expected != Some(address) {
+                blocks.push(Vec::new());
+            }
+            blocks.last_mut().unwrap().push(address);
+            let stops = i.op == Op::Halt || (i.is_jump() && i.taken() == Some(true));
+            expected = if stops { None } else { Some(i.next()) };
+        }
+        blocks
+    }
+
+    /* Drops no-ops from a block by sliding the rest of it up.  If the block
+     * falls through at the end, a jump to where it used to end takes up some
+     * of the space saved, so that's only worth it if two or more instructions
+     * go.
+     */
+    fn compact(&mut self, block: &[usize]) {
+        let instructions: Vec<Instruction> = block.iter().map(|a| self.code[a].clone()).collect();
+        let removed = instructions.iter().filter(|i| is_noop(i)).count();
+        if removed == 0 {
+            return;
+        }
+        let start = block[0];
+        let end = instructions.last().unwrap().next();
+        let mut kept: Vec<Instruction> = instructions.into_iter().filter(|i| !is_noop(i)).collect();
+        let falls_through = match kept.last() {
+            Some(last) => !(last.op == Op::Halt || (last.is_jump() && last.taken() == Some(true))),
+            None => true,
+        };
+        let mut dropped = removed;
+        if falls_through {
+            if removed < 2 {
+                return;
+            }
+            kept.push(jump(0, Param::immediate(end as i64)));
+            dropped -= 1;
+        }
+
+        for address in block {
+            self.code.remove(address);
+        }
+        let mut address = start;
+        for mut i in kept {
+            i.address = address;
+            address = i.next();
+            self.code.insert(i.address, i);
+        }
+        while address < end {
+            self.code.insert(
+                address,
+                Instruction {
+                    address,
+                    op: Op::Halt,
+                    params: vec![],
+                },
+            );
+            address += 1;
+        }
+        self.stats.removed += dropped;
+    }
+
+    fn emit(&self) -> Memory {
+        let mut program = self.analysis.program.clone();
+        for i in self.code.values() {
+            program[i.cells()].copy_from_slice(&i.encode());
+        }
+        program
+    }
+}
+
+/* Rewrites a program into an equivalent which executes fewer instructions,
+ * leaving alone anything which the program might modify or read as data.
+ */
+pub fn optimize(program: &[i64]) -> (Memory, Stats) {
+    let mut optimizer = Optimizer {
+        analysis: Analysis::new(program),
+        code: BTreeMap::new(),
+        known: BTreeMap::new(),
+        stats: Stats::default(),
+    };
+    optimizer.rewrite();
+    optimizer.thread_jumps();
+    for block in optimizer.blocks() {
+        optimizer.compact(&block);
+    }
+    let program = optimizer.emit();
+    (program, optimizer.stats)
+}
+
+/* Runs both programs under the same driver and checks they do the same
+ * thing.  Returns the number of steps each took.
+ */
+pub fn verify(
+    original: &Memory,
+    optimized: &Memory,
+    driver: fn() -> Driver,
+) -> Result<(u64, u64), String> {
+    let before = workload::run(&mut VM::new(original), driver());
+    let after = workload::run(&mut VM::new(optimized), driver());
+    if before.state != after.state {
+        return Err(format!("{:?} became {:?}", before.state, after.state));
+    }
+    if before.output != after.output {
+        let i = before
+            .output
+            .iter()
+            .zip(after.output.iter())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| before.output.len().min(after.output.len()));
+        return Err(format!(
+            "output {} was {:?}, now {:?}",
+            i,
+            before.output.get(i),
+            after.output.get(i)
+        ));
+    }
+    Ok((before.steps, after.steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workload::WORKLOADS;
+
+    #[test]
+    fn test_rewrite() {
+        let mut stats = Stats::default();
+        let program = vec![1102, 6, 7, 20, 1002, 20, 1, 21, 1105, 0, 99, 99];
+        let analysis = Analysis::new(&program);
+        let rewritten: Vec<String> = analysis
+            .instructions
+            .values()
+            .map(|i| rewrite(i, &mut stats).to_string())
+            .collect();
+        assert_eq!(
+            rewritten,
+            vec!["add 42, 0, [20]", "add [20], 0, [21]", "jnz 0, 99", "halt"]
+        );
+        assert_eq!(stats.folded, 1);
+        assert_eq!(stats.moves, 1);
+        assert_eq!(stats.jumps, 0);
+    }
+
+    #[test]
+    fn test_thread_and_compact() {
+        /* 0: jz 0, 6; 3: halt; 4: data; 6: jnz 1, 9; 9: add [4], 0, [4];
+         * 13: jnz 0, 3; 16: out 7; 18: jz 0, 3
+         */
+        let program = vec![
+            1106, 0, 6, 99, 0, 0, 1105, 1, 9, 1001, 4, 0, 4, 1105, 0, 3, 104, 7, 1106, 0, 3,
+        ];
+        let (optimized, stats) = optimize(&program);
+        assert_eq!(stats.threaded, 1);
+        assert_eq!(stats.removed, 2);
+        assert_eq!(&optimized[..3], &[1105, 1, 9]);
+        assert_eq!(&optimized[9..15], &[104, 7, 1105, 1, 3, 99]);
+        assert_eq!(
+            verify(&program, &optimized, || workload::fixed(&[])),
+            Ok((6, 3))
+        );
+    }
+
+    #[test]
+    fn test_known_condition() {
+        /* 0: in [13]; 2: add 1, 0, [14]; 6: jnz 1, 9; 9: jz [14], 0; 12: halt */
+        let program = vec![3, 13, 1101, 1, 0, 14, 1105, 1, 9, 1006, 14, 0, 99, 0, 0];
+        let (optimized, stats) = optimize(&program);
+        assert_eq!(stats.threaded, 1);
+        assert_eq!(&optimized[6..9], &[1105, 1, 12]);
+        assert_eq!(
+            verify(&program, &optimized, || workload::fixed(&[5])),
+            Ok((4, 3))
+        );
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        /* The jump's condition is overwritten before it runs. */
+        let program = vec![1101, 0, 1, 5, 1105, 0, 7, 99];
+        let (optimized, stats) = optimize(&program);
+        assert_eq!(optimized[4..], program[4..]);
+        assert_eq!(stats.skipped, 1);
+        assert!(verify(&program, &optimized, || workload::fixed(&[])).is_ok());
+    }
+
+    #[test]
+    fn test_stack() {
+        /* 0: arb 20; 2: in [rb + 0]; 4: in [17]; 6: mul [17], 1, [18];
+         * 10: out [18]; 12: out [rb + 0]; 14: halt
+         */
+        let program = vec![
+            109, 20, 203, 0, 3, 17, 1002, 17, 1, 18, 4, 18, 204, 0, 99, 0, 0, 0, 0,
+        ];
+        let (optimized, stats) = optimize(&program);
+        /* The relative write can only land past the end of the program. */
+        assert_eq!(stats.skipped, 0);
+        assert_eq!(stats.moves, 1);
+        assert_eq!(&optimized[6..10], &[1001, 17, 0, 18]);
+        assert!(verify(&program, &optimized, || workload::fixed(&[5, 7])).is_ok());
+    }
+
+    #[test]
+    fn test_propagate() {
+        /* 0: add [13], 0, [14]; 4: jz [13], 10; 7: out [14]; 9: halt; 10: out 1; 12: halt; 13: 0 */
+        let program = vec![1001, 13, 0, 14, 1006, 13, 10, 4, 14, 99, 104, 1, 99, 0, 0];
+        let (optimized, stats) = optimize(&program);
+        assert_eq!(stats.propagated, 2);
+        assert_eq!(stats.jumps, 1);
+        assert_eq!(&optimized[..4], &[1101, 0, 0, 14]);
+        assert_eq!(
+            verify(&program, &optimized, || workload::fixed(&[])),
+            Ok((3, 3))
+        );
+    }
+
+    #[test]
+    fn test_data() {
+        let mut faster = 0;
+        for w in WORKLOADS {
+            let program = w.program();
+            let (optimized, _) = optimize(&program);
+            match verify(&program, &optimized, w.driver) {
+                Ok((before, after)) => {
+                    assert!(after <= before, "{} {}", w.name, w.description);
+                    faster += (after < before) as usize;
+                }
+                Err(e) => panic!("{} {}: {}", w.name, w.description, e),
+            }
+        }
+        assert!(faster > 0);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::opcodes::OpcodeTable;
+use crate::{State, VM};
+use std::sync::Arc;
+
+/* A VM's output as an iterator, running the machine only as far as the next
+ * value.  When the program wants input, the callback is asked for some, and
+ * can look at the VM to decide; if it has none, or the program halts, the
+ * iterator ends.  The VM is left where it stopped, so it can be resumed.
+ */
+pub struct Outputs<'a, F> {
+    vm: &'a mut VM,
+    opcodes: Arc<OpcodeTable>,
+    input: F,
+}
+
+impl<'a, F: FnMut(&VM) -> Option<i64>> Outputs<'a, F> {
+    pub(crate) fn new(vm: &'a mut VM, input: F) -> Self {
+        let opcodes = vm.opcodes.clone();
+        Self { vm, opcodes, input }
+    }
+
+    /* Outputs N at a time, eg. the x, y, tile triples day 13 draws with.
+     * A trailing incomplete group is dropped.
+     */
+    pub fn groups<const N: usize>(self) -> Groups<Self, N> {
+        Groups { inner: self }
+    }
+}
+
+impl<'a, F: FnMut(&VM) -> Option<i64>> Iterator for Outputs<'a, F> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        loop {
+            if let Some(value) = self.vm.output.pop_front() {
+                return Some(value);
+            }
+            match self.vm.step(&self.opcodes) {
+                None => {}
+                Some(State::Halted) => return None,
+                Some(State::NeedInput) => {
+                    let value = (self.input)(self.vm)?;
+                    self.vm.input.push_back(value);
+                }
+            }
+        }
+    }
+}
+
+pub struct Groups<I, const N: usize> {
+    inner: I,
+}
+
+impl<I: Iterator<Item = i64>, const N: usize> Iterator for Groups<I, N> {
+    type Item = [i64; N];
+
+    fn next(&mut self) -> Option<[i64; N]> {
+        let mut group = [0; N];
+        for cell in group.iter_mut() {
+            *cell = self.inner.next()?;
+        }
+        Some(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Outputs each input doubled, until it reads a zero. */
+    const DOUBLER: [i64; 16] = [
+        3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+    ];
+
+    #[test]
+    fn test_lazy() {
+        let program = DOUBLER.to_vec();
+        let mut vm = VM::new(&program);
+        let mut asked = 0;
+        {
+            let mut outputs = vm.outputs(|_| {
+                asked += 1;
+                Some(asked)
+            });
+            assert_eq!(outputs.next(), Some(2));
+            assert_eq!(outputs.next(), Some(4));
+        }
+        assert_eq!(asked, 2);
+        assert_eq!(vm.outputs(|_| None).count(), 0);
+        assert_eq!(vm.run(), State::NeedInput);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut vm = VM::new(&DOUBLER.to_vec());
+        let mut inputs = vec![0, 5, 4, 3, 2, 1];
+        let groups: Vec<[i64; 2]> = vm.outputs(|_| inputs.pop()).groups().collect();
+        assert_eq!(groups, vec![[2, 4], [6, 8]]);
+        assert_eq!(vm.run(), State::Halted);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{State, VM};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* Every value that crosses the VM boundary, stamped with the number of
+ * instructions executed before the one that consumed or produced it.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Input { step: u64, value: i64 },
+    Output { step: u64, value: i64 },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input { step, value } => write!(f, "{} in {}", step, value),
+            Event::Output { step, value } => write!(f, "{} out {}", step, value),
+        }
+    }
+}
+
+impl Event {
+    fn parse(line: &str) -> Option<Self> {
+        let bits: Vec<&str> = line.split_whitespace().collect();
+        if bits.len() != 3 {
+            return None;
+        }
+        let step = bits[0].parse().ok()?;
+        let value = bits[2].parse().ok()?;
+        match bits[1] {
+            "in" => Some(Event::Input { step, value }),
+            "out" => Some(Event::Output { step, value }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn inputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.events.iter().filter_map(|e| match e {
+            Event::Input { value, .. } => Some(*value),
+            Event::Output { .. } => None,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Recording {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let events = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                Event::parse(line).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: bad event {:?}", i + 1, line),
+                    )
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { events })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<Event>,
+    pub actual: Option<Event>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |e: &Option<Event>| match e {
+            Some(e) => e.to_string(),
+            None => String::from("nothing"),
+        };
+        write!(
+            f,
+            "event {}: expected {}, got {}",
+            self.index,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+/* Feeds the inputs of a recording back into a VM, checking that everything it
+ * consumes and produces matches the recording.
+ */
+pub struct Replay {
+    recording: Recording,
+    checked: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            checked: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.checked == self.recording.events.len()
+    }
+
+    /* Runs the VM until it halts or needs input that isn't next in the
+     * recording.  Outputs are left in vm.output for the caller to inspect.
+     */
+    pub fn resume(&mut self, vm: &mut VM) -> Result<State, Divergence> {
+        if vm.recording().is_none() {
+            vm.record();
+        }
+        if let Some(Event::Input { value, .. }) = self.recording.events.get(self.checked) {
+            vm.input.push_back(*value);
+        }
+        let before = self.checked;
+        let state = vm.run();
+
+        let actual = &vm.recording().expect("recording enabled above").events;
+        for (index, event) in actual.iter().enumerate().skip(self.checked) {
+            let expected = self.recording.events.get(index);
+            if expected != Some(event) {
+                return Err(Divergence {
+                    index,
+                    expected: expected.copied(),
+                    actual: Some(*event),
+                });
+            }
+        }
+        self.checked = actual.len();
+
+        /* Stopping short of the end is a divergence too, and so is waiting
+         * for input when the recording expects output next, or when nothing
+         * happened, as running again would never get any further.
+         */
+        let next = self.recording.events.get(self.checked);
+        let stuck = match (state, next) {
+            (_, None) => false,
+            (State::Halted, Some(_)) => true,
+            (State::NeedInput, Some(Event::Output { .. })) => true,
+            (State::NeedInput, Some(Event::Input { .. })) => self.checked == before,
+        };
+        if stuck {
+            return Err(Divergence {
+                index: self.checked,
+                expected: next.copied(),
+                actual: None,
+            });
+        }
+        Ok(state)
+    }
+
+    /* Replays the whole recording, returning the state the VM is left in. */
+    pub fn verify(&mut self, vm: &mut VM) -> Result<State, Divergence> {
+        loop {
+            let state = self.resume(vm)?;
+            vm.output.clear();
+            if let State::Halted = state {
+                return Ok(state);
+            }
+            if self.finished() {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+/* What a binary should do with its VM, from --record PATH or --replay PATH. */
+pub enum Session {
+    Live,
+    Record(String),
+    Replay(Recording),
+}
+
+impl Session {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = env::args().skip(1).collect();
+        Self::parse(&args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /* For binaries which take arguments of their own, once they've removed
+     * them.
+     */
+    pub fn parse(args: &[&str]) -> Self {
+        match args {
+            [] => Session::Live,
+            ["--record", path] => Session::Record(path.to_string()),
+            ["--replay", path] => Session::Replay(Recording::load(path).expect(path)),
+            _ => panic!("usage: [--record PATH | --replay PATH]"),
+        }
+    }
+
+    pub fn start(&self, vm: &mut VM) {
+        if let Session::Record(_) = self {
+            vm.record();
+        }
+    }
+
+    pub fn finish(&self, vm: &mut VM) {
+        if let Session::Record(path) = self {
+            let recording = vm.take_recording().expect("recording");
+            recording.save(path).expect(path);
+            eprintln!("recorded {} events to {}", recording.events.len(), path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Echoes the sum of each pair of inputs until it reads a zero. */
+    const ADDER: [i64; 19] = [
+        3, 17, 1006, 17, 16, 3, 18, 1, 17, 18, 18, 4, 18, 1105, 1, 0, 99, 0, 0,
+    ];
+
+    fn record_session(inputs: &[i64]) -> Recording {
+        let mut vm = VM::new(&ADDER.to_vec());
+        vm.record();
+        for &i in inputs {
+            vm.input.push_back(i);
+            vm.run();
+        }
+        vm.take_recording().unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let recording = record_session(&[2, 3, 0]);
+        assert_eq!(
+            recording.events,
+            vec![
+                Event::Input { step: 0, value: 2 },
+                Event::Input { step: 2, value: 3 },
+                Event::Output { step: 4, value: 5 },
+                Event::Input { step: 6, value: 0 },
+            ]
+        );
+        assert_eq!(recording.inputs().collect::<Vec<_>>(), vec![2, 3, 0]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let recording = record_session(&[2, 3, 4, 5, 0]);
+        let parsed: Recording = recording.to_string().parse().unwrap();
+        assert_eq!(parsed, recording);
+        assert!("1 sideways 2".parse::<Recording>().is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let recording = record_session(&[2, 3, 4, 5, 0]);
+        let mut replay = Replay::new(recording);
+        let mut vm = VM::new(&ADDER.to_vec());
+        assert!(matches!(replay.verify(&mut vm), Ok(State::Halted)));
+        assert!(replay.finished());
+    }
+
+    #[test]
+    fn test_divergence() {
+        let mut recording = record_session(&[2, 3, 4, 5, 0]);
+        recording.events[5] = Event::Output {
+            step: 10,
+            value: 10,
+        };
+        let mut replay = Replay::new(recording);
+        let mut vm = VM::new(&ADDER.to_vec());
+        assert_eq!(
+            replay.verify(&mut vm).err(),
+            Some(Divergence {
+                index: 5,
+                expected: Some(Event::Output {
+                    step: 10,
+                    value: 10
+                }),
+                actual: Some(Event::Output { step: 10, value: 9 }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_sync() {
+        /* Without its second input the VM waits for one, while the
+         * recording expects the sum.
+         */
+        let mut recording = record_session(&[2, 3, 4, 5, 0]);
+        recording.events.remove(1);
+        let mut replay = Replay::new(recording);
+        let mut vm = VM::new(&ADDER.to_vec());
+        assert_eq!(
+            replay.verify(&mut vm).err(),
+            Some(Divergence {
+                index: 1,
+                expected: Some(Event::Output { step: 4, value: 5 }),
+                actual: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+
+/* What a search found out: how far each node it reached is from the start,
+ * and which node it came from to get there.
+ */
+#[derive(Debug, Clone)]
+pub struct Search<N> {
+    pub start: N,
+    pub distances: BTreeMap<N, usize>,
+    pub predecessors: BTreeMap<N, N>,
+}
+
+impl<N: Ord + Clone> Search<N> {
+    fn new(start: N) -> Self {
+        let mut distances = BTreeMap::new();
+        distances.insert(start.clone(), 0);
+        Self {
+            start,
+            distances,
+            predecessors: BTreeMap::new(),
+        }
+    }
+
+    /* None if it wasn't reached. */
+    pub fn distance(&self, node: &N) -> Option<usize> {
+        self.distances.get(node).copied()
+    }
+
+    /* The nodes from the start to node, both included. */
+    pub fn path(&self, node: &N) -> Option<Vec<N>> {
+        self.distances.get(node)?;
+        let mut path = vec![node.clone()];
+        while let Some(p) = self.predecessors.get(path.last().unwrap()) {
+            path.push(p.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /* The furthest of the nodes reached, and how far it is.  Starting a
+     * flood at the start, this is how long it takes to fill everywhere.
+     */
+    pub fn furthest(&self) -> (N, usize) {
+        let (node, &distance) = self
+            .distances
+            .iter()
+            .max_by_key(|&(_, d)| d)
+            .expect("the start is always reached");
+        (node.clone(), distance)
+    }
+}
+
+/* Breadth first, where every step costs 1. */
+pub fn bfs<N, F, I>(start: N, mut neighbours: F) -> Search<N>
+where
+    N: Ord + Clone,
+    F: FnMut(&N) -> I,
+    I: IntoIterator<Item = N>,
+{
+    let mut search = Search::new(start.clone());
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(node) = queue.pop_front() {
+        let distance = search.distances[&node] + 1;
+        for next in neighbours(&node) {
+            if !search.distances.contains_key(&next) {
+                search.distances.insert(next.clone(), distance);
+                search.predecessors.insert(next.clone(), node.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+    search
+}
+
+/* How long a flood spreading a step at a time from start takes to reach
+ * everywhere it can.
+ */
+pub fn fill_time<N, F, I>(start: N, neighbours: F) -> usize
+where
+    N: Ord + Clone,
+    F: FnMut(&N) -> I,
+    I: IntoIterator<Item = N>,
+{
+    bfs(start, neighbours).furthest().1
+}
+
+/* Cheapest first, where neighbours come with the cost of stepping to them.
+ * Stops early once goal is settled, if there is one.
+ */
+fn cheapest<N, F, I, H>(start: N, goal: Option<&N>, mut neighbours: F, heuristic: H) -> Search<N>
+where
+    N: Ord + Clone,
+    F: FnMut(&N) -> I,
+    I: IntoIterator<Item = (N, usize)>,
+    H: Fn(&N) -> usize,
+{
+    let mut search = Search::new(start.clone());
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((heuristic(&start), 0, start)));
+    while let Some(Reverse((_, distance, node))) = queue.pop() {
+        if distance > search.distances[&node] {
+            /* Already reached more cheaply. */
+            continue;
+        }
+        if Some(&node) == goal {
+            break;
+        }
+        for (next, cost) in neighbours(&node) {
+            let d = distance + cost;
+            if search.distances.get(&next).is_none_or(|&old| d < old) {
+                search.distances.insert(next.clone(), d);
+                search.predecessors.insert(next.clone(), node.clone());
+                queue.push(Reverse((d + heuristic(&next), d, next)));
+            }
+        }
+    }
+    search
+}
+
+pub fn dijkstra<N, F, I>(start: N, neighbours: F) -> Search<N>
+where
+    N: Ord + Clone,
+    F: FnMut(&N) -> I,
+    I: IntoIterator<Item = (N, usize)>,
+{
+    cheapest(start, None, neighbours, |_| 0)
+}
+
+/* Dijkstra, steered towards goal by a heuristic which must never guess
+ * more than the real cost.  Only the distance to goal is sure to be the
+ * shortest; the search stops once it's found.
+ */
+pub fn astar<N, F, I, H>(start: N, goal: &N, neighbours: F, heuristic: H) -> Search<N>
+where
+    N: Ord + Clone,
+    F: FnMut(&N) -> I,
+    I: IntoIterator<Item = (N, usize)>,
+    H: Fn(&N) -> usize,
+{
+    cheapest(start, Some(goal), neighbours, heuristic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Point;
+
+    const MAZE: &[&str] = &[
+        "#########", //
+        "#S..#...#", //
+        "#.#.#.#.#", //
+        "#.#...#E#", //
+        "#########", //
+    ];
+
+    fn open(p: &Point) -> bool {
+        MAZE[p.y as usize].as_bytes()[p.x as usize] != b'#'
+    }
+
+    fn moves(p: &Point) -> impl Iterator<Item = Point> {
+        p.neighbours().filter(open)
+    }
+
+    #[test]
+    fn test_bfs() {
+        let (start, end) = (Point::new(1, 1), Point::new(7, 3));
+        let search = bfs(start, moves);
+        assert_eq!(search.distance(&end), Some(12));
+        assert_eq!(search.distance(&Point::new(0, 0)), None);
+        let path = search.path(&end).unwrap();
+        assert_eq!(path.len(), 13);
+        assert_eq!((path[0], path[12]), (start, end));
+        for step in path.windows(2) {
+            assert_eq!((step[1] - step[0]).manhattan(), 1);
+        }
+        assert_eq!(search.furthest(), (end, 12));
+        assert_eq!(fill_time(Point::new(5, 2), moves), 9);
+        assert_eq!(search.path(&start), Some(vec![start]));
+    }
+
+    #[test]
+    fn test_weighted() {
+        /* Going round by c is cheaper than the direct road. */
+        let roads = |n: &char| match n {
+            'a' => vec![('b', 7), ('c', 2)],
+            'b' => vec![('d', 1)],
+            'c' => vec![('b', 3), ('d', 9)],
+            _ => vec![],
+        };
+        let search = dijkstra('a', roads);
+        assert_eq!(search.distance(&'d'), Some(6));
+        assert_eq!(search.path(&'d'), Some(vec!['a', 'c', 'b', 'd']));
+
+        let (start, end) = (Point::new(1, 1), Point::new(7, 3));
+        let steps = |p: &Point| moves(p).map(|q| (q, 1)).collect::<Vec<_>>();
+        let search = astar(start, &end, steps, |p| (end - *p).manhattan() as usize);
+        assert_eq!(search.distance(&end), Some(12));
+        assert_eq!(search.path(&end), bfs(start, moves).path(&end));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::opcodes::{Flow, Opcode, Role};
+use crate::VM;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Validation {
+    /* Anything goes, as on the real ship's computer. */
+    Lenient,
+    /* Stop at the first instruction doing something no sane program would. */
+    Strict,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /* Parameters are numbered from 0. */
+    ImmediateWrite { ip: usize, param: usize },
+    /* A read of memory beyond the program, which nothing has written. */
+    ReadPastImage { ip: usize, address: usize },
+    NegativeBase { ip: usize, base: i64 },
+    NegativeJump { ip: usize, target: i64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::ImmediateWrite { ip, param } => {
+                write!(f, "ip {}: write through immediate parameter {}", ip, param)
+            }
+            Violation::ReadPastImage { ip, address } => write!(
+                f,
+                "ip {}: read of uninitialised address {} past the program",
+                ip, address
+            ),
+            Violation::NegativeBase { ip, base } => {
+                write!(f, "ip {}: relative base set to {}", ip, base)
+            }
+            Violation::NegativeJump { ip, target } => {
+                write!(f, "ip {}: jump to {}", ip, target)
+            }
+        }
+    }
+}
+
+/* What a VM in strict mode keeps track of. */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Checker {
+    /* Addresses past the program image which have been written. */
+    written: HashSet<usize>,
+    /* Once a VM has broken the rules it stays stopped. */
+    fault: Option<Violation>,
+}
+
+impl Checker {
+    pub(crate) fn wrote(&mut self, image: usize, address: usize) {
+        if address >= image {
+            self.written.insert(address);
+        }
+    }
+
+    fn initialised(&self, image: usize, address: usize) -> bool {
+        address < image || self.written.contains(&address)
+    }
+
+    fn fail(&mut self, violation: Violation) -> Result<(), Violation> {
+        self.fault = Some(violation);
+        Err(violation)
+    }
+}
+
+/* Checks an instruction's operands before it runs. */
+pub(crate) fn before(vm: &mut VM, opcode: &Opcode) -> Result<(), Violation> {
+    let ip = vm.ip;
+    let instruction = vm.memory.get(ip);
+    let mut violation = None;
+    if let Some(checker) = &vm.checker {
+        if let Some(fault) = checker.fault {
+            return Err(fault);
+        }
+        if !checker.initialised(vm.image, ip) {
+            violation = Some(Violation::ReadPastImage { ip, address: ip });
+        }
+    }
+    for (i, role) in opcode.roles.iter().enumerate() {
+        if violation.is_some() {
+            break;
+        }
+        let mode = instruction / 10_i64.pow(i as u32 + 2) % 10;
+        violation = match role {
+            Role::Write if mode == 1 => Some(Violation::ImmediateWrite { ip, param: i }),
+            Role::Read => {
+                let address = vm.address(i as u32 + 1);
+                match &vm.checker {
+                    Some(checker) if !checker.initialised(vm.image, address) => {
+                        Some(Violation::ReadPastImage { ip, address })
+                    }
+                    _ => None,
+                }
+            }
+            Role::Write => None,
+        };
+    }
+    match (violation, &mut vm.checker) {
+        (Some(violation), Some(checker)) => checker.fail(violation),
+        _ => Ok(()),
+    }
+}
+
+/* Checks where an instruction left the VM, before it moves on. */
+pub(crate) fn after(vm: &mut VM, ip: usize, flow: Flow) -> Result<(), Violation> {
+    let violation = match flow {
+        Flow::Jump(target) if (target as i64) < 0 => Some(Violation::NegativeJump {
+            ip,
+            target: target as i64,
+        }),
+        _ if vm.relative_base < 0 => Some(Violation::NegativeBase {
+            ip,
+            base: vm.relative_base,
+        }),
+        _ => None,
+    };
+    match (violation, &mut vm.checker) {
+        (Some(violation), Some(checker)) => checker.fail(violation),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workload::{self, WORKLOADS};
+    use crate::State;
+
+    fn strict(program: &[i64]) -> VM {
+        let mut vm = VM::new(&program.to_vec());
+        vm.set_validation(Validation::Strict);
+        vm
+    }
+
+    #[test]
+    fn test_immediate_write() {
+        /* The sum would overwrite the instruction's own third operand. */
+        let program = [11101, 5, 5, 3, 99];
+        let mut vm = VM::new(&program.to_vec());
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.memory().get(3), 10);
+
+        let mut vm = strict(&program);
+        let violation = Violation::ImmediateWrite { ip: 0, param: 2 };
+        assert_eq!(vm.try_run(), Err(violation));
+        assert_eq!(vm.memory().get(3), 3);
+        /* It stays stopped. */
+        assert_eq!(vm.try_run(), Err(violation));
+    }
+
+    #[test]
+    fn test_read_past_image() {
+        assert_eq!(
+            strict(&[4, 10, 99]).try_run(),
+            Err(Violation::ReadPastImage { ip: 0, address: 10 })
+        );
+        let mut vm = strict(&[1101, 5, 0, 10, 4, 10, 99]);
+        assert_eq!(vm.try_run(), Ok(State::Halted));
+        assert_eq!(vm.output, vec![5]);
+    }
+
+    #[test]
+    fn test_negative() {
+        assert_eq!(
+            strict(&[109, -1, 99]).try_run(),
+            Err(Violation::NegativeBase { ip: 0, base: -1 })
+        );
+        assert_eq!(
+            strict(&[1105, 1, -3]).try_run(),
+            Err(Violation::NegativeJump { ip: 0, target: -3 })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ip 0: relative base set to -1")]
+    fn test_run_panics() {
+        strict(&[109, -1, 99]).run();
+    }
+
+    #[test]
+    fn test_data() {
+        /* Our real programs behave, apart from day 11's when it starts on
+         * black, where filler instructions read uninitialised memory.
+         */
+        for w in WORKLOADS {
+            let program = w.program();
+            let lenient = workload::run(&mut VM::new(&program), (w.driver)());
+            let mut vm = strict(&program);
+            let mut driver = (w.driver)();
+            let mut output = Vec::new();
+            let result = loop {
+                let result = vm.try_run();
+                output.extend(vm.drain_output());
+                match result {
+                    Ok(State::NeedInput) => match driver(&output) {
+                        Some(value) => vm.input.push_back(value),
+                        None => break result,
+                    },
+                    _ => break result,
+                }
+            };
+            if w.name == "day11-input" && w.description == "painting from black" {
+                let violation = Violation::ReadPastImage {
+                    ip: 37,
+                    address: 1102,
+                };
+                assert_eq!(result, Err(violation));
+            } else {
+                assert_eq!(result, Ok(lenient.state), "{} {}", w.name, w.description);
+                assert_eq!(output, lenient.output);
+            }
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::analysis::{Analysis, Instruction, Mode, Op, Param};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/* Translates an Intcode program ahead of time into a Rust type with the
+ * same interface as VM: new(), run() -> State, and (by dereferencing to the
+ * VM it runs on) input, output, steps() and so on.  It also implements
+ * Machine, so it can take a VM's place in a Network.  Observers and strict
+ * mode work too, but only by running the interpreter instead.
+ *
+ * Each basic block becomes one arm of a match on ip, with operand modes and
+ * addresses resolved at translation time.  Anything the analysis can't find,
+ * or which the program may overwrite, is left to the VM's interpreter; see
+ * compiled::Host.
+ *
+ * This module only depends on analysis, so a build script can use it with
+ *
+ *     #[path = "src/analysis.rs"] mod analysis;
+ *     #[path = "src/transpile.rs"] mod transpile;
+ *
+ * and include!() the result.  `krate` is the path the generated code should
+ * use for this crate, normally "adventofcode2019".
+ */
+pub fn transpile(program: &[i64], name: &str, krate: &str) -> String {
+    let analysis = Analysis::with_entries(program, &tables(program));
+    let compiled: Vec<&Instruction> = analysis
+        .instructions
+        .values()
+        .filter(|i| !i.cells().any(|c| analysis.writes.contains(&c)))
+        .filter(|i| {
+            i.params
+                .iter()
+                .all(|p| p.mode != Mode::Position || p.value >= 0)
+        })
+        .collect();
+    let addresses: BTreeSet<usize> = compiled.iter().map(|i| i.address).collect();
+    let leaders = analysis.leaders();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for instruction in &compiled {
+        match ranges.last_mut() {
+            Some(last) if last.1 >= instruction.address => last.1 = last.1.max(instruction.next()),
+            _ => ranges.push((instruction.address, instruction.next())),
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "/* Generated from Intcode by {}::transpile. */", krate).unwrap();
+    writeln!(out, "#[derive(Clone)]").unwrap();
+    writeln!(out, "pub struct {} {{", name).unwrap();
+    writeln!(out, "    host: {}::compiled::Host,", krate).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#[allow(clippy::all)]").unwrap();
+    writeln!(out, "impl {} {{", name).unwrap();
+    writeln!(
+        out,
+        "    pub const PROGRAM: &'static [i64] = &{:?};",
+        program
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    const COMPILED: &'static [(usize, usize)] = &{:?};",
+        ranges
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    pub fn new() -> Self {{").unwrap();
+    writeln!(out, "        Self {{").unwrap();
+    writeln!(
+        out,
+        "            host: {}::compiled::Host::new(Self::PROGRAM, Self::COMPILED),",
+        krate
+    )
+    .unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    pub fn run(&mut self) -> {}::State {{", krate).unwrap();
+    writeln!(out, "        use {}::State;", krate).unwrap();
+    writeln!(out, "        let h = &mut self.host;").unwrap();
+    writeln!(out, "        loop {{").unwrap();
+    writeln!(out, "            if h.interpreted() {{").unwrap();
+    writeln!(out, "                return h.interpret();").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "            match h.ip() {{").unwrap();
+
+    /* An arm for each block: from each leader, and anywhere else execution
+     * can arrive at compiled code other than by falling through from it.
+     */
+    let continues = |i: &Instruction| !i.is_jump() && i.op != Op::Halt;
+    let fallthroughs: BTreeSet<usize> = compiled
+        .iter()
+        .filter(|i| continues(i))
+        .map(|i| i.next())
+        .collect();
+    for instruction in &compiled {
+        let start = instruction.address;
+        if !leaders.contains(&start) && fallthroughs.contains(&start) {
+            continue;
+        }
+        writeln!(out, "                {} => {{", start).unwrap();
+        let mut instruction = *instruction;
+        loop {
+            emit(&mut out, instruction);
+            let next = instruction.next();
+            if !continues(instruction) {
+                break;
+            }
+            match analysis.instructions.get(&next) {
+                Some(i) if addresses.contains(&next) && !leaders.contains(&next) => instruction = i,
+                _ => {
+                    line(&mut out, &format!("h.jump({});", next));
+                    break;
+                }
+            }
+        }
+        writeln!(out, "                }}").unwrap();
+    }
+
+    writeln!(out, "                _ => {{").unwrap();
+    writeln!(out, "                    if let Some(state) = h.step() {{").unwrap();
+    writeln!(out, "                        return state;").unwrap();
+    writeln!(out, "                    }}").unwrap();
+    writeln!(out, "                }}").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "impl {}::Machine for {} {{", krate, name).unwrap();
+    writeln!(out, "    fn run(&mut self) -> {}::State {{", krate).unwrap();
+    writeln!(out, "        {}::run(self)", name).unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn vm(&self) -> &{}::VM {{", krate).unwrap();
+    writeln!(out, "        self.host.vm()").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn vm_mut(&mut self) -> &mut {}::VM {{", krate).unwrap();
+    writeln!(out, "        self.host.vm_mut()").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    for (deref, method, reference) in &[("Deref", "deref", "&"), ("DerefMut", "deref_mut", "&mut ")]
+    {
+        writeln!(out, "impl std::ops::{} for {} {{", deref, name).unwrap();
+        if *deref == "Deref" {
+            writeln!(out, "    type Target = {}::VM;", krate).unwrap();
+            writeln!(out).unwrap();
+        }
+        writeln!(
+            out,
+            "    fn {}({}self) -> {}{}::VM {{",
+            method, reference, reference, krate
+        )
+        .unwrap();
+        let accessor = if *deref == "Deref" { "vm" } else { "vm_mut" };
+        writeln!(out, "        self.host.{}()", accessor).unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+        writeln!(out).unwrap();
+    }
+    out.pop();
+    out
+}
+
+/* Values in the program which point at otherwise unreachable code, such as
+ * the entries of a jump table indexed through a modified operand.  Each is
+ * only taken if the code found so far doesn't already cover it, so that
+ * values which happen to point into the middle of an instruction are left
+ * alone.
+ */
+fn tables(program: &[i64]) -> Vec<usize> {
+    let mut entries = Vec::new();
+    let mut covered = cells(&Analysis::new(program));
+    for cell in 0..program.len() {
+        let target = program[cell];
+        if covered.contains(&cell)
+            || target <= 0
+            || target as usize >= program.len()
+            || covered.contains(&(target as usize))
+            || Instruction::decode(program, target as usize).is_none()
+        {
+            continue;
+        }
+        entries.push(target as usize);
+        covered = cells(&Analysis::with_entries(program, &entries));
+    }
+    entries
+}
+
+fn cells(analysis: &Analysis) -> BTreeSet<usize> {
+    analysis
+        .instructions
+        .values()
+        .flat_map(|i| i.cells())
+        .collect()
+}
+
+fn line(out: &mut String, text: &str) {
+    writeln!(out, "                    {}", text).unwrap();
+}
+
+fn read(param: &Param) -> String {
+    match param.mode {
+        Mode::Position => format!("h.get({})", param.value),
+        Mode::Immediate => param.value.to_string(),
+        Mode::Relative => format!("h.get_relative({})", param.value),
+    }
+}
+
+/* Stores `value`, leaving compiled code if that overwrote any of it. */
+fn write(out: &mut String, param: &Param, value: &str, next: usize) {
+    match param.mode {
+        Mode::Relative => {
+            line(
+                out,
+                &format!("if h.set_relative({}, {}) {{", param.value, value),
+            );
+            line(out, &format!("    h.jump({});", next));
+            line(out, "    continue;");
+            line(out, "}");
+        }
+        _ => line(out, &format!("h.set({}, {});", param.value, value)),
+    }
+}
+
+fn emit(out: &mut String, instruction: &Instruction) {
+    let params = &instruction.params;
+    let next = instruction.next();
+    line(
+        out,
+        &format!("/* {:>4}  {} */", instruction.address, instruction),
+    );
+    match instruction.op {
+        Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+            let (a, b) = (read(&params[0]), read(&params[1]));
+            let value = match instruction.op {
+                Op::Add => format!("{} + {}", a, b),
+                Op::Mul => format!("{} * {}", a, b),
+                Op::LessThan => format!("({} < {}) as i64", a, b),
+                _ => format!("({} == {}) as i64", a, b),
+            };
+            line(out, &format!("let value = {};", value));
+            line(out, "h.tick();");
+            write(out, &params[2], "value", next);
+        }
+        Op::In => {
+            line(out, "let value = match h.input() {");
+            line(out, "    Some(value) => value,");
+            line(out, "    None => {");
+            line(out, &format!("        h.jump({});", instruction.address));
+            line(out, "        return State::NeedInput;");
+            line(out, "    }");
+            line(out, "};");
+            line(out, "h.tick();");
+            write(out, &params[0], "value", next);
+        }
+        Op::Out => {
+            line(out, &format!("let value = {};", read(&params[0])));
+            line(out, "h.output(value);");
+            line(out, "h.tick();");
+        }
+        Op::AdjustBase => {
+            line(out, &format!("let offset = {};", read(&params[0])));
+            line(out, "h.adjust_base(offset);");
+            line(out, "h.tick();");
+        }
+        Op::JumpIfTrue | Op::JumpIfFalse => {
+            let target = match params[1].constant() {
+                Some(value) => (value as usize).to_string(),
+                None => format!("{} as usize", read(&params[1])),
+            };
+            line(out, "h.tick();");
+            match instruction.taken() {
+                Some(true) => line(out, &format!("h.jump({});", target)),
+                Some(false) => line(out, &format!("h.jump({});", next)),
+                None => {
+                    let test = if instruction.op == Op::JumpIfTrue {
+                        "!="
+                    } else {
+                        "=="
+                    };
+                    line(out, &format!("if {} {} 0 {{", read(&params[0]), test));
+                    line(out, &format!("    h.jump({});", target));
+                    line(out, "} else {");
+                    line(out, &format!("    h.jump({});", next));
+                    line(out, "}");
+                }
+            }
+        }
+        Op::Halt => {
+            line(out, &format!("h.jump({});", instruction.address));
+            line(out, "return State::Halted;");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::Profiler;
+    use crate::strict::Validation;
+    use crate::workload::{self, WORKLOADS};
+    use crate::{Machine, VM};
+
+    mod generated {
+        extern crate self as adventofcode2019;
+
+        include!(concat!(env!("OUT_DIR"), "/day2.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day5.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day7.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day9.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day11.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day13.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day13play.rs"));
+        include!(concat!(env!("OUT_DIR"), "/day15.rs"));
+    }
+
+    /* Runs the compiled program against the interpreter on each workload
+     * with the same program, which must agree on everything observable.
+     */
+    fn check<M: Machine>(program: &[i64], new: fn() -> M) {
+        let workloads: Vec<_> = WORKLOADS
+            .iter()
+            .filter(|w| w.program() == program)
+            .collect();
+        assert!(!workloads.is_empty());
+        for w in workloads {
+            let mut vm = VM::new(&program.to_vec());
+            let mut compiled = new();
+            vm.record();
+            compiled.vm_mut().record();
+
+            let expected = workload::run(&mut vm, (w.driver)());
+            let actual = workload::run(&mut compiled, (w.driver)());
+            assert_eq!(actual, expected, "{} {}", w.name, w.description);
+            let compiled = compiled.vm();
+            assert_eq!(
+                compiled.recording().unwrap().events,
+                vm.recording().unwrap().events
+            );
+            assert!(compiled.memory().to_vec() == vm.memory().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_data() {
+        use generated::*;
+        check(Day2::PROGRAM, Day2::new);
+        check(Day5::PROGRAM, Day5::new);
+        check(Day7::PROGRAM, Day7::new);
+        check(Day9::PROGRAM, Day9::new);
+        check(Day11::PROGRAM, Day11::new);
+        check(Day13::PROGRAM, Day13::new);
+        check(Day13Play::PROGRAM, Day13Play::new);
+        check(Day15::PROGRAM, Day15::new);
+    }
+
+    #[test]
+    fn test_observed() {
+        /* Observers see everything, as if nothing had been compiled. */
+        let mut vm = VM::new(&generated::Day9::PROGRAM.to_vec());
+        let mut compiled = generated::Day9::new();
+        vm.observe(Profiler::new());
+        compiled.observe(Profiler::new());
+        vm.input.push_back(1);
+        compiled.input.push_back(1);
+        assert_eq!(compiled.run(), vm.run());
+        let (a, b) = (
+            compiled.observer::<Profiler>().unwrap(),
+            vm.observer::<Profiler>().unwrap(),
+        );
+        assert_eq!(a.by_opcode(), b.by_opcode());
+    }
+
+    #[test]
+    #[should_panic(expected = "ip 37")]
+    fn test_strict() {
+        /* Day 11 reads past its image when it starts on black. */
+        let mut compiled = generated::Day11::new();
+        compiled.set_validation(Validation::Strict);
+        workload::run(&mut compiled, workload::painter(0));
+    }
+
+    #[test]
+    fn test_transpile() {
+        /* Reads a value, outputs it doubled and loops. */
+        let program = [3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+        let code = transpile(&program, "Doubler", "crate");
+        assert!(code.contains("pub struct Doubler {"));
+        assert!(code.contains("const COMPILED: &'static [(usize, usize)] = &[(0, 11)];"));
+        assert!(code.contains("let value = h.get(11) * 2;"));
+
+        /* The output instruction's operand is overwritten, so it has to be
+         * left to the interpreter.
+         */
+        let code = transpile(&[1101, 2, 3, 5, 4, 0, 99], "Modified", "crate");
+        assert!(code.contains("const COMPILED: &'static [(usize, usize)] = &[(0, 4), (6, 7)];"));
+    }
+}
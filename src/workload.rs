@@ -0,0 +1,265 @@
+use crate::grid::{Direction, Point, SparseGrid};
+use crate::{data_program, program_from_str, Machine, Memory, State};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* Decides a program's next input from everything it has output so far, or
+ * returns None to stop it there.
+ */
+pub type Driver = Box<dyn FnMut(&[i64]) -> Option<i64>>;
+
+/* A program in data/ and a way to run it: with fixed input for the puzzles
+ * which take some, and playing along for the interactive ones, so that they
+ * run to the end the way the puzzles do.
+ */
+pub struct Workload {
+    pub name: &'static str,
+    pub description: &'static str,
+    /* Cells to change before running, as day 2 sets its noun and verb. */
+    pub patch: &'static [(usize, i64)],
+    pub driver: fn() -> Driver,
+}
+
+pub const WORKLOADS: &[Workload] = &[
+    Workload {
+        name: "input-day2",
+        description: "1202",
+        patch: &[(1, 12), (2, 2)],
+        driver: || fixed(&[]),
+    },
+    Workload {
+        name: "input-day5",
+        description: "input 1",
+        patch: &[],
+        driver: || fixed(&[1]),
+    },
+    Workload {
+        name: "input-day5",
+        description: "input 5",
+        patch: &[],
+        driver: || fixed(&[5]),
+    },
+    Workload {
+        name: "input-day7",
+        description: "phase 3, signal 17",
+        patch: &[],
+        driver: || fixed(&[3, 17]),
+    },
+    Workload {
+        name: "input-day9",
+        description: "input 1",
+        patch: &[],
+        driver: || fixed(&[1]),
+    },
+    Workload {
+        name: "input-day9",
+        description: "input 2",
+        patch: &[],
+        driver: || fixed(&[2]),
+    },
+    Workload {
+        name: "day11-input",
+        description: "painting from black",
+        patch: &[],
+        driver: || painter(0),
+    },
+    Workload {
+        name: "day11-input",
+        description: "painting from white",
+        patch: &[],
+        driver: || painter(1),
+    },
+    Workload {
+        name: "day13-input",
+        description: "drawing the screen",
+        patch: &[],
+        driver: || fixed(&[]),
+    },
+    Workload {
+        name: "day13-input",
+        description: "playing to the end",
+        patch: &[(0, 2)],
+        driver: arcade,
+    },
+    Workload {
+        name: "day15-input",
+        description: "exploring the maze",
+        patch: &[],
+        driver: explorer,
+    },
+];
+
+impl Workload {
+    /* The program, patched, from the data directory. */
+    pub fn program(&self) -> Memory {
+        self.patched(data_program(self.name))
+    }
+
+    /* The same, from a copy of the data directory elsewhere. */
+    pub fn program_in<P: AsRef<Path>>(&self, dir: P) -> io::Result<Memory> {
+        let source = fs::read_to_string(dir.as_ref().join(self.name))?;
+        Ok(self.patched(program_from_str(&source)))
+    }
+
+    fn patched(&self, mut program: Memory) -> Memory {
+        for &(address, value) in self.patch {
+            program[address] = value;
+        }
+        program
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Run {
+    pub output: Vec<i64>,
+    pub state: State,
+    pub steps: u64,
+}
+
+/* Runs a machine until it halts or the driver stops it. */
+pub fn run(machine: &mut dyn Machine, mut driver: Driver) -> Run {
+    let mut output = Vec::new();
+    let state = loop {
+        let state = machine.run();
+        output.extend(machine.vm_mut().drain_output());
+        if state == State::Halted {
+            break state;
+        }
+        match driver(&output) {
+            Some(value) => machine.vm_mut().input.push_back(value),
+            None => break state,
+        }
+    };
+    Run {
+        output,
+        state,
+        steps: machine.vm().steps(),
+    }
+}
+
+pub fn fixed(inputs: &'static [i64]) -> Driver {
+    let mut inputs = inputs.iter().copied();
+    Box::new(move |_| inputs.next())
+}
+
+/* Day 11's robot: reports the colour of the panel it's on, then paints it
+ * and turns as told.
+ */
+pub fn painter(start: i64) -> Driver {
+    let mut hull: SparseGrid<i64> = SparseGrid::new();
+    hull.insert(Point::default(), start);
+    let (mut at, mut heading) = (Point::default(), Direction::N);
+    let mut seen = 0;
+    Box::new(move |output| {
+        for pair in output[seen..].chunks_exact(2) {
+            hull.insert(at, pair[0]);
+            heading = if pair[1] == 0 {
+                heading.left()
+            } else {
+                heading.right()
+            };
+            at = heading.advance(at);
+            seen += 2;
+        }
+        Some(*hull.get(&at).unwrap_or(&0))
+    })
+}
+
+/* Day 13's joystick, keeping the paddle under the ball. */
+pub fn arcade() -> Driver {
+    let (mut ball, mut paddle) = (0, 0);
+    let mut seen = 0;
+    Box::new(move |output| {
+        for tile in output[seen..].chunks_exact(3) {
+            match tile {
+                [-1, 0, _] => {}
+                [x, _, 3] => paddle = *x,
+                [x, _, 4] => ball = *x,
+                _ => {}
+            }
+            seen += 3;
+        }
+        Some((ball - paddle).signum())
+    })
+}
+
+/* Day 15's droid, exploring every cell it can reach depth first and
+ * stopping once it has come back to the start.
+ */
+pub fn explorer() -> Driver {
+    let command = |d: Direction| match d {
+        Direction::N => 1,
+        Direction::S => 2,
+        Direction::W => 3,
+        Direction::E => 4,
+    };
+    let mut known = BTreeSet::new();
+    known.insert(Point::<i64>::default());
+    let mut at = Point::default();
+    let mut path: Vec<Direction> = Vec::new();
+    /* The last move, and whether it was back the way the droid came. */
+    let mut last: Option<(Direction, bool)> = None;
+    Box::new(move |output| {
+        if let Some((d, back)) = last.take() {
+            match output.last() {
+                Some(0) => {}
+                Some(_) if back => at = d.advance(at),
+                Some(_) => {
+                    at = d.advance(at);
+                    path.push(d);
+                }
+                None => panic!("no status for a move"),
+            }
+        }
+        let unexplored = Direction::ALL
+            .iter()
+            .copied()
+            .find(|d| !known.contains(&d.advance(at)));
+        let (d, back) = match unexplored {
+            Some(d) => {
+                known.insert(d.advance(at));
+                (d, false)
+            }
+            None => (path.pop()?.reverse(), true),
+        };
+        last = Some((d, back));
+        Some(command(d))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VM;
+
+    #[test]
+    fn test_drivers() {
+        let results: Vec<Run> = WORKLOADS
+            .iter()
+            .map(|w| run(&mut VM::new(&w.program()), (w.driver)()))
+            .collect();
+        /* Everything but the maze runs until the program halts. */
+        for (w, r) in WORKLOADS.iter().zip(&results) {
+            assert_eq!(
+                r.state == State::Halted,
+                w.name != "day15-input",
+                "{}",
+                w.name
+            );
+        }
+        assert_eq!(results[6].output.len(), 2 * 9971);
+        /* Day 13 is played until every block is gone, for 15410 points. */
+        assert_eq!(
+            results[9].output[results[9].output.len() - 3..],
+            [-1, 0, 15410]
+        );
+        /* Day 15's droid goes into each of the 798 open cells besides the
+         * start and back out again, finding one oxygen system.
+         */
+        let statuses = &results[10].output;
+        assert_eq!(statuses.iter().filter(|&&s| s != 0).count(), 2 * 798);
+        assert_eq!(statuses.iter().filter(|&&s| s == 2).count(), 1);
+    }
+}